@@ -0,0 +1,19 @@
+use serde::Deserialize;
+
+/// Audit log of every scanned job and every action on it, read from an `[audit]` table.
+#[derive(Deserialize, Default, Debug, PartialEq, Clone)]
+pub struct AuditConfig {
+    /// File receiving a JSON object per line, no audit log when unset
+    pub path: Option<String>,
+    /// Size in bytes the file is rotated at, never rotated when unset
+    pub max_size: Option<u64>,
+    /// Rotated files kept, named after the file with a `.1`, `.2`... suffix
+    pub keep: Option<u32>,
+}
+
+impl AuditConfig {
+    /// Rotated files kept, 5 by default.
+    pub fn keep(&self) -> u32 {
+        self.keep.unwrap_or(5)
+    }
+}
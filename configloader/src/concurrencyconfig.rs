@@ -0,0 +1,35 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+/// Deployments played one after another, read from a `[concurrency]` table.
+#[derive(Deserialize, Default, Debug, PartialEq, Clone)]
+pub struct ConcurrencyConfig {
+    /// Play a single job at a time in each project
+    pub per_project: Option<bool>,
+    /// Projects sharing a target, playing a single job at a time among all of them
+    pub groups: Option<HashMap<String, Vec<u64>>>,
+}
+
+impl ConcurrencyConfig {
+    pub fn per_project(&self) -> bool {
+        self.per_project.unwrap_or(false)
+    }
+
+    /// Name of the group whose jobs are played one at a time with the project ones.
+    ///
+    /// A named group takes precedence over the project one, the first name in alphabetical
+    /// order being taken for projects listed in several groups.
+    pub fn group(&self, project_id: u64) -> Option<String> {
+        let named = self
+            .groups
+            .iter()
+            .flatten()
+            .filter(|(_, projects)| projects.contains(&project_id))
+            .map(|(name, _)| name)
+            .min()
+            .cloned();
+
+        named.or_else(|| self.per_project().then(|| format!("project {project_id}")))
+    }
+}
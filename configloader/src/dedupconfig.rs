@@ -0,0 +1,46 @@
+use serde::Deserialize;
+
+/// What makes two manual jobs of a project duplicates of each other.
+#[derive(Deserialize, Default, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupKey {
+    /// Every manual job of the project
+    #[default]
+    Project,
+    /// Jobs with the same name
+    JobName,
+    /// Jobs deploying to the same environment
+    Environment,
+    /// Jobs of pipelines for the same branch
+    Branch,
+    /// Jobs deploying the same tag of the same source project
+    SourceTag,
+}
+
+/// Which of the duplicated jobs is kept, the others being canceled.
+#[derive(Deserialize, Default, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupKeep {
+    /// The one of the highest pipeline id
+    #[default]
+    Pipeline,
+    /// The one with the highest semantic version tag, ignoring a leading `v`
+    Semver,
+}
+
+/// How duplicated jobs are found, read from a `[dedup]` table.
+#[derive(Deserialize, Default, Debug, PartialEq, Clone, Copy)]
+pub struct DedupConfig {
+    pub key: Option<DedupKey>,
+    pub keep: Option<DedupKeep>,
+}
+
+impl DedupConfig {
+    pub fn key(&self) -> DedupKey {
+        self.key.unwrap_or_default()
+    }
+
+    pub fn keep(&self) -> DedupKeep {
+        self.keep.unwrap_or_default()
+    }
+}
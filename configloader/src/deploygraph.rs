@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+/// Deploy order between projects and jobs, read from a `[needs]` table.
+///
+/// Each key is played only after every listed prerequisite succeeded. Numbers are project
+/// ids, standing for every job of the project, other names are job names.
+#[derive(Deserialize, Default, Debug, PartialEq, Clone)]
+#[serde(transparent)]
+pub struct DeployGraph(pub HashMap<String, Vec<String>>);
+
+impl DeployGraph {
+    /// Prerequisites of a job, from its project and its name entries.
+    pub fn needs(&self, project_id: u64, job_name: Option<&str>) -> Vec<&str> {
+        self.0
+            .iter()
+            .filter(|(node, _)| Self::is_node(node, project_id, job_name))
+            .flat_map(|(_, needs)| needs.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// Whether a node stands for a job.
+    pub fn is_node(node: &str, project_id: u64, job_name: Option<&str>) -> bool {
+        node.parse::<u64>()
+            .map_or_else(|_| job_name == Some(node), |id| id == project_id)
+    }
+
+    /// Nodes needing themselves through their prerequisites, sorted.
+    pub fn cycles(&self) -> Vec<&str> {
+        let mut cyclic = self
+            .0
+            .keys()
+            .filter(|node| self.reaches(node, node))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        cyclic.sort_unstable();
+        cyclic
+    }
+
+    /// Whether `target` is a direct or indirect prerequisite of `node`.
+    fn reaches(&self, node: &str, target: &str) -> bool {
+        let mut visited = vec![];
+        let mut next = vec![node];
+
+        while let Some(current) = next.pop() {
+            for need in self.0.get(current).into_iter().flatten() {
+                if need == target {
+                    return true;
+                }
+                if !visited.contains(&need.as_str()) {
+                    visited.push(need);
+                    next.push(need);
+                }
+            }
+        }
+        false
+    }
+}
@@ -0,0 +1,146 @@
+use chrono::{DateTime, Datelike, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::Deserialize;
+
+/// A weekly period of time in which manual jobs may be played.
+///
+/// A window whose `end` is before its `start` crosses midnight, so
+/// `start = "22:00"` and `end = "02:00"` on `Fri` lasts until Saturday 02:00.
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct DeployWindow {
+    /// Week days when the window opens, every day if empty
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    #[serde(default = "default_timezone")]
+    pub timezone: Tz,
+}
+
+fn default_timezone() -> Tz {
+    Tz::UTC
+}
+
+impl DeployWindow {
+    fn opens_on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+
+    /// Checks if the window is open at the given moment.
+    pub fn is_open(&self, now: DateTime<Utc>) -> bool {
+        let local = now.with_timezone(&self.timezone);
+        let (day, time) = (local.weekday(), local.time());
+
+        if self.start <= self.end {
+            self.opens_on(day) && self.start <= time && time < self.end
+        } else {
+            (self.opens_on(day) && time >= self.start)
+                || (self.opens_on(day.pred()) && time < self.end)
+        }
+    }
+
+    /// Next moment, after `now`, when the window opens.
+    pub fn next_opening(&self, now: DateTime<Utc>) -> Option<DateTime<Tz>> {
+        now.with_timezone(&self.timezone)
+            .date_naive()
+            .iter_days()
+            .take(8)
+            .filter(|date| self.opens_on(date.weekday()))
+            .filter_map(|date| {
+                self.timezone
+                    .from_local_datetime(&date.and_time(self.start))
+                    .earliest()
+            })
+            .find(|opening| opening > &now)
+    }
+}
+
+/// Set of deploy windows, jobs may be played while any of them is open.
+///
+/// An empty schedule means no restriction at all.
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Default)]
+#[serde(transparent)]
+pub struct DeploySchedule(pub Vec<DeployWindow>);
+
+impl DeploySchedule {
+    pub fn is_open(&self, now: DateTime<Utc>) -> bool {
+        self.0.is_empty() || self.0.iter().any(|window| window.is_open(now))
+    }
+
+    /// Earliest opening among all windows, shown in its window timezone.
+    pub fn next_opening(&self, now: DateTime<Utc>) -> Option<DateTime<Tz>> {
+        self.0
+            .iter()
+            .filter_map(|window| window.next_opening(now))
+            .min_by_key(|opening| opening.with_timezone(&Utc))
+    }
+}
+
+#[cfg(test)]
+mod test_deploy_window {
+    use super::*;
+
+    fn window() -> DeployWindow {
+        toml::from_str(
+            r#"
+            days = ["Tue", "Wed", "Thu"]
+            start = "10:00"
+            end = "16:00"
+            timezone = "America/Sao_Paulo"
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn utc(text: &str) -> DateTime<Utc> {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn test_open_inside_window() {
+        // Wednesday 12:00 in Sao Paulo (UTC-3)
+        assert!(window().is_open(utc("2026-10-21T15:00:00Z")));
+    }
+
+    #[test]
+    fn test_closed_outside_window() {
+        // Wednesday 09:00 in Sao Paulo, before start
+        assert!(!window().is_open(utc("2026-10-21T12:00:00Z")));
+        // Wednesday 16:00 in Sao Paulo, end is exclusive
+        assert!(!window().is_open(utc("2026-10-21T19:00:00Z")));
+        // Monday 12:00 in Sao Paulo
+        assert!(!window().is_open(utc("2026-10-19T15:00:00Z")));
+    }
+
+    #[test]
+    fn test_next_opening() {
+        // Thursday 17:00 in Sao Paulo, next is Tuesday 10:00
+        let next = window().next_opening(utc("2026-10-22T20:00:00Z")).unwrap();
+        assert_eq!(next.with_timezone(&Utc), utc("2026-10-27T13:00:00Z"));
+
+        // Wednesday 08:00 in Sao Paulo, opens on the same day
+        let next = window().next_opening(utc("2026-10-21T11:00:00Z")).unwrap();
+        assert_eq!(next.with_timezone(&Utc), utc("2026-10-21T13:00:00Z"));
+    }
+
+    #[test]
+    fn test_window_over_midnight() {
+        let window = DeployWindow {
+            days: vec![Weekday::Fri],
+            start: NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(2, 0, 0).unwrap(),
+            timezone: Tz::UTC,
+        };
+
+        assert!(window.is_open(utc("2026-10-23T23:00:00Z")));
+        assert!(window.is_open(utc("2026-10-24T01:00:00Z")));
+        assert!(!window.is_open(utc("2026-10-24T03:00:00Z")));
+        assert!(!window.is_open(utc("2026-10-23T01:00:00Z")));
+    }
+
+    #[test]
+    fn test_empty_schedule_is_always_open() {
+        assert!(DeploySchedule::default().is_open(utc("2026-10-19T03:00:00Z")));
+        assert!(!DeploySchedule(vec![window()]).is_open(utc("2026-10-19T03:00:00Z")));
+    }
+}
@@ -0,0 +1,22 @@
+use serde::Deserialize;
+
+/// How the configured group is scanned for projects, read from a `[group]` table.
+#[derive(Deserialize, Default, Debug, PartialEq, Clone)]
+pub struct GroupConfig {
+    /// Also scan the projects of subgroups
+    pub include_subgroups: Option<bool>,
+    /// Levels of subgroups scanned below the group, all of them when unset
+    pub max_depth: Option<u32>,
+    /// Also scan projects shared with the group, as Gitlab lists them by default
+    pub include_shared: Option<bool>,
+}
+
+impl GroupConfig {
+    pub fn include_subgroups(&self) -> bool {
+        self.include_subgroups.unwrap_or(false)
+    }
+
+    pub fn include_shared(&self) -> bool {
+        self.include_shared.unwrap_or(true)
+    }
+}
@@ -0,0 +1,37 @@
+use core::time::Duration;
+
+use serde::Deserialize;
+
+/// Settings of the Gitlab API client, read from a `[http]` table.
+#[derive(Deserialize, Default, Debug, PartialEq, Clone)]
+pub struct HttpConfig {
+    /// Seconds to establish a connection
+    pub connect_timeout: Option<u64>,
+    /// Seconds waiting for Gitlab to answer, and again for it to send the answer body
+    pub read_timeout: Option<u64>,
+    /// Seconds for a whole request, the answer included
+    pub timeout: Option<u64>,
+    /// Value of the `User-Agent` header
+    pub user_agent: Option<String>,
+    /// Seconds an idle connection is kept to be reused, 0 to close it after each request
+    pub keep_alive: Option<u64>,
+}
+
+impl HttpConfig {
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        self.connect_timeout.map(Duration::from_secs)
+    }
+
+    pub fn read_timeout(&self) -> Option<Duration> {
+        self.read_timeout.map(Duration::from_secs)
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout.map(Duration::from_secs)
+    }
+
+    /// Idle connections lifetime, 90 seconds by default.
+    pub fn keep_alive(&self) -> Duration {
+        Duration::from_secs(self.keep_alive.unwrap_or(90))
+    }
+}
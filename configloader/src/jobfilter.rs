@@ -0,0 +1,184 @@
+use regex::Regex;
+use serde::Deserialize;
+
+/// A glob like `deploy-*`, or a regular expression between slashes like `/^deploy-(eu|us)$/`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(try_from = "String")]
+pub struct JobPattern {
+    source: String,
+    regex: Regex,
+}
+
+impl TryFrom<String> for JobPattern {
+    type Error = regex::Error;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        let expression = match source
+            .strip_prefix('/')
+            .and_then(|inner| inner.strip_suffix('/'))
+        {
+            Some(expression) => expression.to_owned(),
+            None => glob_expression(&source),
+        };
+
+        Ok(JobPattern {
+            regex: Regex::new(&expression)?,
+            source,
+        })
+    }
+}
+
+impl PartialEq for JobPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl core::fmt::Display for JobPattern {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl JobPattern {
+    /// A glob only, even between slashes, like the protected tag names of Gitlab.
+    pub fn glob(source: &str) -> Result<Self, regex::Error> {
+        Ok(JobPattern {
+            regex: Regex::new(&glob_expression(source))?,
+            source: source.to_owned(),
+        })
+    }
+
+    pub fn matches(&self, value: &str) -> bool {
+        self.regex.is_match(value)
+    }
+}
+
+/// Anchored expression for a glob, where `*` matches any text and `?` any character.
+fn glob_expression(glob: &str) -> String {
+    let mut expression = String::from("^");
+    for part in glob.split_inclusive(['*', '?']) {
+        let (literal, wildcard) = match part.strip_suffix('*') {
+            Some(literal) => (literal, ".*"),
+            None => part
+                .strip_suffix('?')
+                .map_or((part, ""), |literal| (literal, ".")),
+        };
+        expression.push_str(&regex::escape(literal));
+        expression.push_str(wildcard);
+    }
+    expression.push('$');
+    expression
+}
+
+/// Patterns a job attribute must match, and the ones it mustn't.
+#[derive(Deserialize, Default, Debug, PartialEq, Clone)]
+pub struct PatternFilter {
+    /// Any value passes when unset
+    pub include: Option<Vec<JobPattern>>,
+    pub exclude: Option<Vec<JobPattern>>,
+}
+
+impl PatternFilter {
+    /// Whether a value passes, a missing one only failing include patterns.
+    pub fn passes(&self, value: Option<&str>) -> bool {
+        let included = self.include.as_ref().is_none_or(|patterns| {
+            value.is_some_and(|value| patterns.iter().any(|pattern| pattern.matches(value)))
+        });
+        let excluded = self.exclude.as_ref().is_some_and(|patterns| {
+            value.is_some_and(|value| patterns.iter().any(|pattern| pattern.matches(value)))
+        });
+
+        included && !excluded
+    }
+}
+
+/// Which manual jobs are managed, read from a `[jobs]` table. The others are left untouched.
+#[derive(Deserialize, Default, Debug, PartialEq, Clone)]
+pub struct JobFilter {
+    pub name: Option<PatternFilter>,
+    pub stage: Option<PatternFilter>,
+    pub environment: Option<PatternFilter>,
+}
+
+impl JobFilter {
+    /// Whether a job with these attributes is managed.
+    pub fn matches(
+        &self,
+        name: Option<&str>,
+        stage: Option<&str>,
+        environment: Option<&str>,
+    ) -> bool {
+        [
+            (&self.name, name),
+            (&self.stage, stage),
+            (&self.environment, environment),
+        ]
+        .iter()
+        .all(|(filter, value)| filter.as_ref().is_none_or(|filter| filter.passes(*value)))
+    }
+}
+
+#[cfg(test)]
+mod test_job_filter {
+    use super::*;
+
+    fn pattern(source: &str) -> JobPattern {
+        JobPattern::try_from(source.to_owned()).unwrap()
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(pattern("deploy-*").matches("deploy-prod"));
+        assert!(!pattern("deploy-*").matches("pre-deploy-prod"));
+        assert!(pattern("v?.*").matches("v1.2"));
+        assert!(!pattern("v?.*").matches("v12"));
+        assert!(pattern("cleanup").matches("cleanup"));
+        assert!(!pattern("cleanup").matches("cleanup-all"));
+    }
+
+    #[test]
+    fn test_protected_glob() {
+        let glob = |source: &str| JobPattern::glob(source).unwrap();
+        assert!(glob("v*").matches("v1.2.0"));
+        assert!(glob("v1.2.0").matches("v1.2.0"));
+        assert!(!glob("v1.2.0").matches("v1.2.0-rc"));
+        assert!(!glob("v1.2.0").matches("v1a2b0"));
+        assert!(glob("release-*-final").matches("release-2024-final"));
+        assert!(glob("release-*-final").matches("release--final"));
+        assert!(!glob("release-*-final").matches("release-2024"));
+        assert!(glob("*").matches("anything"));
+        assert!(glob("*-*").matches("a-b"));
+        assert!(!glob("*-*").matches("ab"));
+        assert!(!glob("v*").matches("1.2.0"));
+        assert!(glob("/v1/").matches("/v1/"));
+    }
+
+    #[test]
+    fn test_regex() {
+        assert!(pattern("/^deploy-(eu|us)$/").matches("deploy-eu"));
+        assert!(!pattern("/^deploy-(eu|us)$/").matches("deploy-asia"));
+        assert!(pattern("/teardown/").matches("nightly-teardown-env"));
+        assert!(JobPattern::try_from("/(/".to_owned()).is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let filter: JobFilter = toml::from_str(
+            r#"
+            name.include=["deploy*"]
+            name.exclude=["*-dry"]
+            environment.exclude=["/^review/"]
+            "#,
+        )
+        .unwrap();
+
+        assert!(filter.matches(Some("deploy"), None, None));
+        assert!(filter.matches(Some("deploy-prod"), Some("deploy"), Some("production")));
+        assert!(!filter.matches(Some("deploy-dry"), None, None));
+        assert!(!filter.matches(Some("cleanup"), None, None));
+        assert!(!filter.matches(None, None, None));
+        assert!(!filter.matches(Some("deploy"), None, Some("review/feature")));
+        assert!(JobFilter::default().matches(None, None, None));
+    }
+}
@@ -3,38 +3,107 @@
 // extern crate envy;
 // extern crate merge;
 // extern crate toml;
+mod auditconfig;
+mod concurrencyconfig;
+mod dedupconfig;
+mod deploygraph;
+mod deploywindow;
+mod groupconfig;
+mod httpconfig;
+mod jobfilter;
+mod logformat;
+mod metricsconfig;
+mod projectconfig;
+mod retryconfig;
 mod smtpconfig;
-
+mod tagrules;
+
+use std::collections::HashMap;
+
+pub use auditconfig::AuditConfig;
+pub use concurrencyconfig::ConcurrencyConfig;
+pub use dedupconfig::{DedupConfig, DedupKeep, DedupKey};
+pub use deploygraph::DeployGraph;
+pub use deploywindow::{DeploySchedule, DeployWindow};
+pub use groupconfig::GroupConfig;
+pub use httpconfig::HttpConfig;
+pub use jobfilter::{JobFilter, JobPattern, PatternFilter};
 use log::{debug, error};
+pub use logformat::LogFormat;
 use merge::Merge;
+pub use metricsconfig::MetricsConfig;
+pub use projectconfig::ProjectConfig;
+pub use retryconfig::RetryConfig;
 use serde::Deserialize;
-pub use smtpconfig::SmtpConfig;
+pub use smtpconfig::{MailTemplate, SmtpConfig};
+pub use tagrules::{TagRules, TagViolation};
 
 pub mod prelude {
     pub use super::Config;
     pub use super::SmtpConfig;
+    pub use super::{
+        AuditConfig, ConcurrencyConfig, DedupConfig, DedupKeep, DedupKey, DeployGraph,
+        DeploySchedule, DeployWindow, GroupConfig, HttpConfig, JobFilter, LogFormat, MailTemplate,
+        MetricsConfig, ProjectConfig, RetryConfig, TagRules, TagViolation,
+    };
 }
 
 /// Uses serde crates *(toml and envy)* to be feeded from **.env** file or from environment variables
-#[derive(Deserialize, Debug, Merge, PartialEq, Clone)]
+#[derive(Deserialize, Debug, Default, Merge, PartialEq, Clone)]
 #[serde(rename_all = "lowercase")]
 pub struct Config {
     pub group_id: Option<u64>,
     pub project_id: Option<u64>,
+    /// More groups to scan, besides `group_id`
+    pub group_ids: Option<Vec<u64>>,
+    /// More projects to scan, besides `project_id`
+    pub project_ids: Option<Vec<u64>>,
+    /// Projects left out of scans, even when listed or inside a scanned group
+    pub exclude_projects: Option<Vec<u64>>,
+    pub group: Option<GroupConfig>,
     pub private_token: Option<String>,
     pub base_url: Option<String>,
     pub production_tag_key: Option<String>,
     pub max_wait_time: Option<u64>,
+    pub scan_interval: Option<u64>,
+    pub dry_run: Option<bool>,
+    /// `human` (default) or `json` log lines
+    pub log_format: Option<LogFormat>,
+    /// Append-only file recording handled jobs across runs
+    pub state_file: Option<String>,
+    pub deploy_windows: Option<DeploySchedule>,
+    pub jobs: Option<JobFilter>,
+    pub tags: Option<TagRules>,
+    pub dedup: Option<DedupConfig>,
+    pub concurrency: Option<ConcurrencyConfig>,
+    /// Prerequisites of projects and jobs, played before them in a same run
+    pub needs: Option<DeployGraph>,
+    pub audit: Option<AuditConfig>,
+    pub metrics: Option<MetricsConfig>,
     pub smtp: Option<SmtpConfig>,
+    pub retry: Option<RetryConfig>,
+    pub http: Option<HttpConfig>,
+    /// Per project settings, keyed by project id
+    pub projects: Option<HashMap<String, ProjectConfig>>,
 }
 
 impl Config {
     /// Method to read configurations from environment variables or from file.
     pub fn load_config() -> Result<Config, &'static str> {
+        Self::load_from_vars(std::env::vars())
+    }
+
+    /// Read configurations from the given variables, as if they were the environment, and
+    /// from the file named by their `ENV_FILE` (`.env` by default).
+    pub fn load_from_vars<I>(vars: I) -> Result<Config, &'static str>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let vars = vars.into_iter().collect::<Vec<_>>();
         let mut config;
 
         // Load config from environment variables
-        match envy::from_env::<Config>() {
+        match envy::from_iter::<_, Config>(vars.clone()) {
             Ok(env_config) => {
                 config = env_config;
             }
@@ -45,25 +114,29 @@ impl Config {
         };
 
         // SMTP settings from environment variables
-        if std::env::vars().any(|(k, _)| k.starts_with("SMTP_")) {
+        if vars.iter().any(|(k, _)| k.starts_with("SMTP_")) {
             let mut smtp_config = SmtpConfig::default();
 
-            std::env::vars()
+            vars.iter()
                 .filter(|(k, _)| k.starts_with("SMTP_"))
                 .for_each(|(k, v)| match k.to_uppercase().as_str() {
-                    "SMTP_USER" => smtp_config.user = Some(v),
-                    "SMTP_SERVER" => smtp_config.server = Some(v),
-                    "SMTP_PASS" => smtp_config.pass = Some(v),
-                    "SMTP_FROM" => smtp_config.from = Some(v),
-                    "SMTP_TO" => smtp_config.to = Some(v),
-                    "SMTP_SUBJECT" => smtp_config.subject = Some(v),
+                    "SMTP_USER" => smtp_config.user = Some(v.clone()),
+                    "SMTP_SERVER" => smtp_config.server = Some(v.clone()),
+                    "SMTP_PASS" => smtp_config.pass = Some(v.clone()),
+                    "SMTP_FROM" => smtp_config.from = Some(v.clone()),
+                    "SMTP_TO" => smtp_config.to = Some(v.clone()),
+                    "SMTP_SUBJECT" => smtp_config.subject = Some(v.clone()),
+                    "SMTP_DIGEST" => smtp_config.digest = v.parse().ok(),
                     _ => {}
                 });
 
             config.smtp = Some(smtp_config);
         }
 
-        let env_file = std::env::var("ENV_FILE").unwrap_or(".env".to_string());
+        let env_file = vars
+            .iter()
+            .find(|(k, _)| k == "ENV_FILE")
+            .map_or(".env".to_string(), |(_, v)| v.clone());
 
         if let Ok(content) = std::fs::read_to_string(&env_file) {
             debug!("Reading {} file.", &env_file);
@@ -90,11 +163,127 @@ impl Config {
         };
         if config.base_url.is_none() {
             error!("There's no gitlab server to scan");
-            std::process::exit(1);
+            return Err("There's no gitlab server to scan");
         }
 
         Ok(config)
     }
+
+    /// Look for missing or invalid settings, describing each problem found.
+    pub fn check(&self) -> Vec<String> {
+        let mut problems = vec![];
+
+        match &self.base_url {
+            Some(url) if url.starts_with("http://") || url.starts_with("https://") => {}
+            Some(url) => problems.push(format!("base_url \"{url}\" isn't a http(s) address")),
+            None => problems.push("base_url is missing".to_owned()),
+        }
+        if self.private_token.as_ref().is_none_or(String::is_empty) {
+            problems.push("private_token is missing".to_owned());
+        }
+        if self.scan_groups().is_empty() && self.scan_projects().is_empty() {
+            problems.push("There's no group_id nor project_id to scan".to_owned());
+        }
+        if self.scan_interval == Some(0) {
+            problems.push("scan_interval must be greater than zero".to_owned());
+        }
+        if self.retry.as_ref().and_then(|retry| retry.attempts) == Some(0) {
+            problems.push("retry attempts must be greater than zero".to_owned());
+        }
+        if let Some(needs) = &self.needs {
+            let cycles = needs.cycles();
+            if !cycles.is_empty() {
+                problems.push(format!(
+                    "needs of {} depend on themselves",
+                    cycles.join(", ")
+                ));
+            }
+        }
+        if let Some(metrics) = &self.metrics {
+            if let Some(listen) = metrics
+                .listen
+                .as_ref()
+                .filter(|_| metrics.listen_addr().is_none())
+            {
+                problems.push(format!(
+                    "metrics listen \"{listen}\" isn't an ip:port address"
+                ));
+            }
+        }
+        if let Some(smtp) = &self.smtp {
+            if !smtp.is_valid() {
+                problems.push("smtp settings are incomplete or invalid".to_owned());
+            }
+        }
+
+        problems
+    }
+
+    /// Groups to scan, `group_id` first.
+    pub fn scan_groups(&self) -> Vec<u64> {
+        Self::ids(self.group_id, self.group_ids.as_ref())
+    }
+
+    /// Projects to scan on their own, `project_id` first.
+    pub fn scan_projects(&self) -> Vec<u64> {
+        Self::ids(self.project_id, self.project_ids.as_ref())
+    }
+
+    /// Whether a project is left out of scans.
+    pub fn is_excluded(&self, project_id: u64) -> bool {
+        self.exclude_projects
+            .as_ref()
+            .is_some_and(|excluded| excluded.contains(&project_id))
+    }
+
+    fn ids(single: Option<u64>, list: Option<&Vec<u64>>) -> Vec<u64> {
+        let mut ids = single.into_iter().collect::<Vec<_>>();
+        for id in list.into_iter().flatten() {
+            if !ids.contains(id) {
+                ids.push(*id);
+            }
+        }
+        ids
+    }
+
+    /// Settings specific to a project, if any.
+    pub fn project(&self, project_id: u64) -> Option<&ProjectConfig> {
+        self.projects.as_ref()?.get(&project_id.to_string())
+    }
+
+    /// Filter of managed jobs for a project, the project one taking precedence over the global one.
+    pub fn job_filter(&self, project_id: u64) -> Option<&JobFilter> {
+        self.project(project_id)
+            .and_then(|project| project.jobs.as_ref())
+            .or(self.jobs.as_ref())
+    }
+
+    /// Rules of deploy tags for a project, the project ones taking precedence over the global ones.
+    pub fn tag_rules(&self, project_id: u64) -> Option<&TagRules> {
+        self.project(project_id)
+            .and_then(|project| project.tags.as_ref())
+            .or(self.tags.as_ref())
+    }
+
+    /// Deduplication of jobs for a project, the project one taking precedence over the global one.
+    pub fn dedup(&self, project_id: u64) -> DedupConfig {
+        self.project(project_id)
+            .and_then(|project| project.dedup)
+            .or(self.dedup)
+            .unwrap_or_default()
+    }
+
+    /// Concurrency group of a project, whose jobs are played one at a time.
+    pub fn concurrency_group(&self, project_id: u64) -> Option<String> {
+        self.concurrency.as_ref()?.group(project_id)
+    }
+
+    /// Deploy windows for a project, the project ones taking precedence over the global ones.
+    pub fn deploy_schedule(&self, project_id: u64) -> Option<&DeploySchedule> {
+        self.project(project_id)
+            .and_then(|project| project.deploy_windows.as_ref())
+            .or(self.deploy_windows.as_ref())
+    }
 }
 
 #[cfg(test)]
@@ -145,17 +334,198 @@ mod test_load_config {
         let config_new = Config {
             group_id: None,
             project_id: None,
+            group_ids: None,
+            project_ids: None,
+            exclude_projects: None,
+            group: None,
             private_token: None,
             base_url: None,
             production_tag_key: None,
             // max_wait_time: Some(30),
             max_wait_time: None,
+            scan_interval: None,
+            dry_run: None,
+            log_format: None,
+            state_file: None,
+            deploy_windows: None,
+            jobs: None,
+            tags: None,
+            dedup: None,
+            concurrency: None,
+            needs: None,
+            audit: None,
+            metrics: None,
             smtp: None,
+            retry: None,
+            http: None,
+            projects: None,
         };
 
         assert_eq!(confs, config_new);
     }
 
+    #[test]
+    fn test_check() {
+        let confs: Config = toml::from_str(
+            r#"
+            base_url="gitlab.com"
+            scan_interval=0
+
+            [retry]
+            attempts=0
+
+            [metrics]
+            listen="localhost"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            confs.check(),
+            vec![
+                "base_url \"gitlab.com\" isn't a http(s) address",
+                "private_token is missing",
+                "There's no group_id nor project_id to scan",
+                "scan_interval must be greater than zero",
+                "retry attempts must be greater than zero",
+                "metrics listen \"localhost\" isn't an ip:port address",
+            ]
+        );
+
+        let confs: Config = toml::from_str(
+            r#"
+            base_url="https://gitlab.com/"
+            private_token="XXXXXXXXXXXXX"
+            group_id=1
+            "#,
+        )
+        .unwrap();
+
+        assert!(confs.check().is_empty());
+    }
+
+    #[test]
+    fn test_scan_targets() {
+        let confs: Config = toml::from_str(
+            r#"
+            group_id=1
+            group_ids=[2, 1, 3]
+            project_ids=[10, 11]
+            exclude_projects=[11]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(confs.scan_groups(), vec![1, 2, 3]);
+        assert_eq!(confs.scan_projects(), vec![10, 11]);
+        assert!(confs.is_excluded(11));
+        assert!(!confs.is_excluded(10));
+        assert!(confs
+            .check()
+            .contains(&"private_token is missing".to_owned()));
+        assert!(!confs
+            .check()
+            .contains(&"There's no group_id nor project_id to scan".to_owned()));
+    }
+
+    #[test]
+    fn test_deploy_schedule_precedence() {
+        let confs: Config = toml::from_str(
+            r#"
+            base_url="https://gitlab.com/"
+
+            [[deploy_windows]]
+            days=["Tue", "Wed", "Thu"]
+            start="10:00"
+            end="16:00"
+            timezone="America/Sao_Paulo"
+
+            [projects.123]
+            deploy_windows=[]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(confs.deploy_schedule(1).unwrap().0.len(), 1);
+        assert_eq!(confs.deploy_schedule(123), Some(&DeploySchedule::default()));
+    }
+
+    #[test]
+    fn test_dedup_precedence() {
+        let confs: Config = toml::from_str(
+            r#"
+            [dedup]
+            key="environment"
+
+            [projects.123.dedup]
+            key="source_tag"
+            keep="semver"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(confs.dedup(1).key(), DedupKey::Environment);
+        assert_eq!(confs.dedup(1).keep(), DedupKeep::Pipeline);
+        assert_eq!(confs.dedup(123).key(), DedupKey::SourceTag);
+        assert_eq!(confs.dedup(123).keep(), DedupKeep::Semver);
+        assert_eq!(Config::default().dedup(1), DedupConfig::default());
+    }
+
+    #[test]
+    fn test_concurrency_group() {
+        let confs: Config = toml::from_str(
+            r#"
+            [concurrency]
+            per_project=true
+
+            [concurrency.groups]
+            shared_db=[20, 30]
+            billing=[30]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(confs.concurrency_group(20), Some("shared_db".to_owned()));
+        assert_eq!(confs.concurrency_group(30), Some("billing".to_owned()));
+        assert_eq!(confs.concurrency_group(40), Some("project 40".to_owned()));
+        assert_eq!(Config::default().concurrency_group(40), None);
+    }
+
+    #[test]
+    fn test_deploy_graph() {
+        let confs: Config = toml::from_str(
+            r#"
+            [needs]
+            "30"=["20"]
+            "deploy-frontend"=["deploy-backend", "10"]
+            "20"=["10"]
+            "#,
+        )
+        .unwrap();
+        let needs = confs.needs.clone().unwrap();
+
+        assert_eq!(needs.needs(20, Some("deploy-backend")), ["10"]);
+        let mut frontend = needs.needs(30, Some("deploy-frontend"));
+        frontend.sort_unstable();
+        assert_eq!(frontend, ["10", "20", "deploy-backend"]);
+        assert!(needs.needs(10, Some("migrate")).is_empty());
+        assert!(needs.cycles().is_empty());
+
+        let confs: Config = toml::from_str(
+            r#"
+            [needs]
+            "a"=["b"]
+            "b"=["c"]
+            "c"=["a"]
+            "d"=["a"]
+            "#,
+        )
+        .unwrap();
+        assert!(confs
+            .check()
+            .contains(&"needs of a, b, c depend on themselves".to_owned()));
+    }
+
     #[test]
     #[ignore = "concurrency"]
     fn test_set_read_env() {
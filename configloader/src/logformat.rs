@@ -0,0 +1,12 @@
+use serde::Deserialize;
+
+/// How log lines are written, read from `log_format`.
+#[derive(Deserialize, Default, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Plain lines, for people reading them
+    #[default]
+    Human,
+    /// A JSON object per line, with the fields of the job it's about
+    Json,
+}
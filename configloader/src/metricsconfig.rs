@@ -0,0 +1,19 @@
+use core::net::SocketAddr;
+
+use serde::Deserialize;
+
+/// Prometheus metrics exposition, read from a `[metrics]` table.
+#[derive(Deserialize, Default, Debug, PartialEq, Clone)]
+pub struct MetricsConfig {
+    /// Address serving `/metrics` over http while running as daemon, like `0.0.0.0:9184`
+    pub listen: Option<String>,
+    /// File rewritten with the metrics after each scan, for the node exporter textfile collector
+    pub textfile: Option<String>,
+}
+
+impl MetricsConfig {
+    /// Parsed `listen` address, `None` when unset or invalid.
+    pub fn listen_addr(&self) -> Option<SocketAddr> {
+        self.listen.as_ref()?.parse().ok()
+    }
+}
@@ -0,0 +1,13 @@
+use serde::Deserialize;
+
+use crate::{DedupConfig, DeploySchedule, JobFilter, TagRules};
+
+/// Settings overriding the global ones for a single project, read from a
+/// `[projects.<project id>]` table.
+#[derive(Deserialize, Default, Debug, PartialEq, Clone)]
+pub struct ProjectConfig {
+    pub deploy_windows: Option<DeploySchedule>,
+    pub jobs: Option<JobFilter>,
+    pub tags: Option<TagRules>,
+    pub dedup: Option<DedupConfig>,
+}
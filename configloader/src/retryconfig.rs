@@ -0,0 +1,31 @@
+use core::time::Duration;
+
+use serde::Deserialize;
+
+/// Retry policy for Gitlab API calls, read from a `[retry]` table.
+#[derive(Deserialize, Default, Debug, PartialEq, Clone)]
+pub struct RetryConfig {
+    /// Total tries of a request, the first one included
+    pub attempts: Option<u32>,
+    /// Delay before the first retry in milliseconds, doubled on each new try
+    pub base_delay: Option<u64>,
+    /// Longest delay between two tries in milliseconds
+    pub max_delay: Option<u64>,
+}
+
+impl RetryConfig {
+    /// Total tries of a request, 3 by default.
+    pub fn attempts(&self) -> u32 {
+        self.attempts.unwrap_or(3).max(1)
+    }
+
+    /// Delay before the first retry, half a second by default.
+    pub fn base_delay(&self) -> Duration {
+        Duration::from_millis(self.base_delay.unwrap_or(500))
+    }
+
+    /// Longest delay between two tries, a minute by default.
+    pub fn max_delay(&self) -> Duration {
+        Duration::from_millis(self.max_delay.unwrap_or(60_000))
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use lettre::message::Mailboxes;
 use merge::Merge;
 use serde::Deserialize;
@@ -13,6 +15,20 @@ pub struct SmtpConfig {
     pub from: Option<String>,
     pub to: Option<String>,
     pub subject: Option<String>,
+    /// Send a single report per recipient after each run, instead of a mail per job
+    pub digest: Option<bool>,
+    /// Template files replacing the built-in subject and bodies, by mail reason
+    pub templates: Option<HashMap<String, MailTemplate>>,
+}
+
+/// Template files of the mails sent for a reason, each falling back to the built-in one.
+#[derive(Deserialize, Default, Debug, PartialEq, Eq, Clone)]
+pub struct MailTemplate {
+    pub subject: Option<String>,
+    /// Html body, its values escaped
+    pub html: Option<String>,
+    /// Plain text alternative of the body
+    pub text: Option<String>,
 }
 
 impl SmtpConfig {
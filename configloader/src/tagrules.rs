@@ -0,0 +1,156 @@
+use core::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+
+use crate::JobPattern;
+
+/// Rules a deploy tag must follow, read from a `[tags]` table.
+#[derive(Deserialize, Default, Debug, PartialEq, Clone)]
+pub struct TagRules {
+    /// Glob or /regular expression/ the tag must match
+    pub pattern: Option<JobPattern>,
+    /// Semantic version requirement, like `">=1.4, <2"`, a leading `v` being ignored
+    pub semver: Option<VersionReq>,
+    /// Refuse pre-release versions, like `1.4.0-rc.1`
+    pub reject_prerelease: Option<bool>,
+    /// Oldest tag accepted, in seconds
+    pub max_age: Option<u64>,
+    /// Refuse tags not matching a protected tag of the source project
+    pub require_protected: Option<bool>,
+}
+
+/// The tag rule a deploy tag broke.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub enum TagViolation {
+    Pattern(String),
+    Semver(String),
+    Prerelease,
+    TooOld(u64),
+    UnknownAge,
+}
+
+impl Display for TagViolation {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            TagViolation::Pattern(pattern) => write!(f, "doesn't match pattern {pattern}"),
+            TagViolation::Semver(requirement) => {
+                write!(f, "isn't a version matching {requirement}")
+            }
+            TagViolation::Prerelease => write!(f, "is a pre-release"),
+            TagViolation::TooOld(max_age) => write!(f, "is older than {max_age} seconds"),
+            TagViolation::UnknownAge => write!(f, "has no date to check its age"),
+        }
+    }
+}
+
+impl TagRules {
+    pub fn require_protected(&self) -> bool {
+        self.require_protected.unwrap_or(false)
+    }
+
+    /// Check a tag created at `created_at`, returning the first broken rule.
+    pub fn check(
+        &self,
+        tag: &str,
+        created_at: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Result<(), TagViolation> {
+        if let Some(pattern) = &self.pattern {
+            if !pattern.matches(tag) {
+                return Err(TagViolation::Pattern(pattern.to_string()));
+            }
+        }
+
+        let version = Version::parse(tag.strip_prefix('v').unwrap_or(tag)).ok();
+        if let Some(requirement) = &self.semver {
+            if !version
+                .as_ref()
+                .is_some_and(|version| requirement.matches(version))
+            {
+                return Err(TagViolation::Semver(requirement.to_string()));
+            }
+        }
+        if self.reject_prerelease.unwrap_or(false)
+            && version.is_some_and(|version| !version.pre.is_empty())
+        {
+            return Err(TagViolation::Prerelease);
+        }
+
+        if let Some(max_age) = self.max_age {
+            let created_at = created_at.ok_or(TagViolation::UnknownAge)?;
+            let age = now.signed_duration_since(created_at).num_seconds();
+            if u64::try_from(age).is_ok_and(|age| age > max_age) {
+                return Err(TagViolation::TooOld(max_age));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_tag_rules {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let rules: TagRules = toml::from_str(
+            r#"
+            pattern="v*"
+            semver=">=1.4, <2"
+            reject_prerelease=true
+            max_age=86400
+            "#,
+        )
+        .unwrap();
+        let now = Utc::now();
+        let fresh = Some(now - chrono::Duration::hours(1));
+
+        assert_eq!(rules.check("v1.4.2", fresh, now), Ok(()));
+        assert_eq!(
+            rules.check("1.4.2", fresh, now),
+            Err(TagViolation::Pattern("v*".to_owned()))
+        );
+        assert_eq!(
+            rules.check("v2.0.0", fresh, now),
+            Err(TagViolation::Semver(">=1.4, <2".to_owned()))
+        );
+        assert_eq!(
+            rules.check("vNext", fresh, now),
+            Err(TagViolation::Semver(">=1.4, <2".to_owned()))
+        );
+        assert_eq!(
+            rules.check("v1.5.0-rc.1", fresh, now),
+            Err(TagViolation::Semver(">=1.4, <2".to_owned()))
+        );
+        assert_eq!(
+            rules.check("v1.4.2", Some(now - chrono::Duration::days(2)), now),
+            Err(TagViolation::TooOld(86400))
+        );
+        assert_eq!(
+            rules.check("v1.4.2", None, now),
+            Err(TagViolation::UnknownAge)
+        );
+    }
+
+    #[test]
+    fn test_prerelease() {
+        let rules = TagRules {
+            reject_prerelease: Some(true),
+            ..Default::default()
+        };
+        let now = Utc::now();
+
+        assert_eq!(
+            rules.check("v1.5.0-rc.1", None, now),
+            Err(TagViolation::Prerelease)
+        );
+        assert_eq!(rules.check("v1.5.0", None, now), Ok(()));
+        // Not a version, so not a pre-release either
+        assert_eq!(rules.check("PROD-1.1", None, now), Ok(()));
+        assert_eq!(TagRules::default().check("anything", None, now), Ok(()));
+    }
+}
@@ -0,0 +1,86 @@
+use std::fmt::Display;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+
+/// Errors got while talking to the Gitlab API.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The request couldn't be sent or its response couldn't be read
+    Transport(reqwest::Error),
+    /// Gitlab took longer than the read timeout to answer
+    Timeout(Duration),
+    /// Gitlab answered with an unsuccessful status
+    Status { status: StatusCode, body: String },
+    /// Gitlab refused the private token
+    Authentication { status: StatusCode, body: String },
+    /// The response body isn't the expected json
+    Json {
+        source: serde_json::Error,
+        body: String,
+    },
+    /// The request url couldn't be built
+    InvalidUrl { url: String, reason: String },
+    /// A configuration needed to call the API is missing or invalid
+    Config(&'static str),
+    /// The job lacks an information needed to call the API
+    IncompleteJob(&'static str),
+}
+
+impl Error {
+    /// Status Gitlab answered with, when it answered.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::Status { status, .. } | Error::Authentication { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Classify an unsuccessful response status.
+    pub fn from_status(status: StatusCode, body: String) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                Error::Authentication { status, body }
+            }
+            _ => Error::Status { status, body },
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Transport(error) => write!(f, "Error while calling Gitlab: {error}"),
+            Error::Timeout(after) => write!(f, "Gitlab didn't answer in {after:?}"),
+            Error::Status { status, body } => write!(f, "Gitlab answered {status}: {body}"),
+            Error::Authentication { status, body } => {
+                write!(f, "Gitlab refused the private token ({status}): {body}")
+            }
+            Error::Json { source, body } => {
+                write!(f, "Error while parsing to json ({source}) from: \n{body}")
+            }
+            Error::InvalidUrl { url, reason } => {
+                write!(f, "Error while parsing url \"{url}\": {reason}")
+            }
+            Error::Config(field) => write!(f, "Missing or invalid configuration: {field}"),
+            Error::IncompleteJob(field) => write!(f, "Job without {field}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Transport(error) => Some(error),
+            Error::Json { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(error: reqwest::Error) -> Self {
+        Error::Transport(error)
+    }
+}
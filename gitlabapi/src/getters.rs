@@ -2,156 +2,174 @@
 
 use std::collections::{HashMap, HashSet};
 
+use futures::stream::{self, StreamExt};
+
 use crate::prelude::*;
 // use crate::getters_traits::*;
 
 impl GitlabJOB {
     /// Get a tuple from an option with serde_json::Value and number of pages as u64
-    pub async fn get_json(&self, url: &String) -> Result<(Value, u64), String> {
-        let resp = self.api_get(url);
+    pub async fn get_json(&self, url: &str) -> Result<(Value, u64), Error> {
+        let resp = self.api_get(url)?;
         debug!("Getting json from: {url}");
 
-        match resp.send().await {
-            Err(e) => Err(format!("Error while getting {url}: {}", e)),
-            Ok(response) => {
-                let headers = response.headers().clone();
-                match response.text().await {
-                    Ok(text) => {
-                        let num_pages: u64 = match headers.get("x-total-pages") {
-                            Some(total_pages) => {
-                                if let Ok(num_str) = total_pages.to_str() {
-                                    num_str.parse().unwrap_or(1)
-                                } else {
-                                    1
-                                }
-                            }
-                            None => 1,
-                        };
-
-                        // debug!("Path \"{url}\" gave json:\n{text}");
-                        Self::parse_json(text).map(|val| (val, num_pages))
-                    }
-                    Err(e) => Err(e.to_string()),
+        let response = self.send_request(resp, true).await?;
+        let num_pages: u64 = match response.headers().get("x-total-pages") {
+            Some(total_pages) => {
+                if let Ok(num_str) = total_pages.to_str() {
+                    num_str.parse().unwrap_or(1)
+                } else {
+                    1
                 }
             }
-        }
+            None => 1,
+        };
+
+        let text = self.response_text(response).await?;
+        // debug!("Path \"{url}\" gave json:\n{text}");
+        Self::parse_json(text).map(|val| (val, num_pages))
     }
 
     /// Recover trigger variables from a Gitlab pipeline.
-    pub async fn get_pipe_vars(&self, projid: u64, pipelineid: u64) -> HashMap<String, String> {
+    pub async fn get_pipe_vars(
+        &self,
+        projid: u64,
+        pipelineid: u64,
+    ) -> Result<HashMap<String, String>, Error> {
         let uri = format!("/api/v4/projects/{projid}/pipelines/{pipelineid}/variables");
 
         let mut hashmap_out: HashMap<String, String> = HashMap::new();
 
-        let mut new_uri;
-        let mut current_page = 1;
-
-        loop {
-            new_uri = format!("{}?per_page=100&page={}", &uri, current_page);
-            let num_pages;
-
-            if let Ok((vars_obj, pages)) = self.get_json(&new_uri).await {
-                num_pages = pages;
-                if let Some(vec_vars) = vars_obj.as_array() {
-                    vec_vars.iter().for_each(|var| {
-                        if let Some(key) = var["key"].as_str() {
-                            if let Some(value) = var["value"].as_str() {
-                                hashmap_out.insert(key.to_owned(), value.to_owned());
-                            }
-                        }
-                    });
+        self.get_all(&uri, Pagination::Offset)
+            .await?
+            .iter()
+            .for_each(|var| {
+                if let Some(key) = var["key"].as_str() {
+                    if let Some(value) = var["value"].as_str() {
+                        hashmap_out.insert(key.to_owned(), value.to_owned());
+                    }
                 }
-            } else {
-                num_pages = 1;
-            };
-
-            if current_page >= num_pages {
-                break;
-            }
-            current_page += 1;
-        }
+            });
 
-        hashmap_out
+        Ok(hashmap_out)
     }
 
-    /// Get projects ids from a Gitlab group
-    pub async fn get_projs(&self, groupid: GroupID) -> HashSet<u64> {
-        // if self.config.group_id.is_none() {
-        //     return vec![];
-        // };
-
-        let base_uri = format!(
-            "/api/v4/groups/{}/projects?pagination=keyset&simple=true&per_page=100&order_by=id&sort=asc",
-            // self.config.group_id.unwrap()
-            groupid.0
-        );
-
+    /// Get projects ids from a Gitlab group, and from its subgroups when configured.
+    pub async fn get_projs(&self, groupid: GroupID) -> Result<HashSet<u64>, Error> {
+        let settings = self.config.group.clone().unwrap_or_default();
+
+        let groups = if settings.include_subgroups() {
+            self.get_subgroups(groupid, settings.max_depth).await?
+        } else {
+            vec![groupid.0]
+        };
+
+        let mut stream_groups = stream::iter(groups)
+            .map(|group| {
+                let uri = format!(
+                    "/api/v4/groups/{group}/projects?simple=true&order_by=id&sort=asc&with_shared={}",
+                    settings.include_shared()
+                );
+                async move { self.get_all(&uri, Pagination::Keyset).await }
+            })
+            .buffer_unordered(STREAM_BUFF_SIZE);
+
+        // Projects shared with several groups are listed once
         let mut vec_projs = HashSet::new();
+        while let Some(projects) = stream_groups.next().await {
+            vec_projs.extend(projects?.iter().filter_map(|proj| proj["id"].as_u64()));
+        }
 
-        let mut current_page = 1;
-
-        loop {
-            let new_uri = format!("{}&page={}", &base_uri, current_page);
-            let num_pages;
+        Ok(vec_projs)
+    }
 
-            if let Ok((json, total_pages)) = self.get_json(&new_uri).await {
-                num_pages = total_pages;
-                if let Some(vec_json) = json.as_array() {
-                    vec_json.iter().for_each(|proj| {
-                        if let Some(val) = proj["id"].as_u64() {
-                            vec_projs.insert(val);
-                        }
-                    });
+    /// Get a group id and its descendants ones, up to `max_depth` levels below it.
+    pub async fn get_subgroups(
+        &self,
+        groupid: GroupID,
+        max_depth: Option<u32>,
+    ) -> Result<Vec<u64>, Error> {
+        let mut found = vec![groupid.0];
+        let mut visited = HashSet::from([groupid.0]);
+        let mut level = vec![groupid.0];
+        let mut depth = 0;
+
+        while !level.is_empty() && max_depth.is_none_or(|max| depth < max) {
+            let children = stream::iter(level)
+                .map(|group| async move {
+                    let uri = format!("/api/v4/groups/{group}/subgroups?order_by=id&sort=asc");
+                    self.get_all(&uri, Pagination::Offset).await
+                })
+                .buffer_unordered(STREAM_BUFF_SIZE)
+                .collect::<Vec<_>>()
+                .await;
+
+            level = vec![];
+            for subgroups in children {
+                for id in subgroups?.iter().filter_map(|group| group["id"].as_u64()) {
+                    if visited.insert(id) {
+                        level.push(id);
+                        found.push(id);
+                    }
                 }
-            } else {
-                num_pages = 1;
-            }
-
-            if current_page >= num_pages {
-                break;
             }
-            current_page += 1;
+            depth += 1;
         }
 
-        vec_projs
+        Ok(found)
     }
 
     /// Inspect a project for its git tags.
-    pub async fn get_tags(&self, id: ProjectID) -> Vec<String> {
+    pub async fn get_tags(&self, id: ProjectID) -> Result<Vec<String>, Error> {
         let url = format!("api/v4/projects/{}/repository/tags?order_by=updated", id.0);
 
-        let mut got_tags = vec![];
+        Ok(self
+            .get_all(&url, Pagination::Offset)
+            .await?
+            .iter()
+            .filter_map(|tag| tag["name"].as_str())
+            .map(str::to_owned)
+            .collect())
+    }
+
+    /// Look a git tag up by its name, `None` when the project hasn't it.
+    pub async fn get_tag(&self, id: ProjectID, name: &str) -> Result<Option<GitTag>, Error> {
+        let url = format!(
+            "/api/v4/projects/{}/repository/tags/{}",
+            id.0,
+            Self::encode_segment(name)
+        );
 
-        if let Ok((resp, _)) = self.get_json(&url).await {
-            if let Some(tags_list) = resp.as_array() {
-                tags_list.iter().for_each(|tag| {
-                    if let Some(tag_name) = tag["name"].as_str() {
-                        got_tags.push(tag_name.to_owned())
-                    }
-                })
+        match self.get_json(&url).await {
+            Ok((json, _)) => Ok(GitTag::from_json(&json)),
+            Err(Error::Status { status, .. }) if status == reqwest::StatusCode::NOT_FOUND => {
+                Ok(None)
             }
+            Err(error) => Err(error),
         }
+    }
 
-        got_tags
+    /// Get the protected tag names of a project, which may hold `*` wildcards.
+    pub async fn get_protected_tags(&self, id: ProjectID) -> Result<Vec<String>, Error> {
+        let url = format!("/api/v4/projects/{}/protected_tags", id.0);
+
+        Ok(self
+            .get_all(&url, Pagination::Offset)
+            .await?
+            .iter()
+            .filter_map(|tag| tag["name"].as_str())
+            .map(str::to_owned)
+            .collect())
     }
 
     /// Get current status of a job
-    pub async fn get_status(&self, job: &JobInfo) -> JobScope {
-        let uri = format!(
-            "/api/v4/projects/{}/jobs/{}",
-            job.proj_id.unwrap(),
-            job.id.unwrap()
-        );
-
-        match self.get_json(&uri).await {
-            Ok((resp, _)) => {
-                if let Some(json) = resp.get("status") {
-                    json.to_string().trim().to_owned().into()
-                } else {
-                    JobScope::Invalid
-                }
-            }
-            Err(_) => JobScope::Invalid,
-        }
+    pub async fn get_status(&self, job: &JobInfo) -> Result<JobScope, Error> {
+        let uri = Self::job_path(job)?;
+
+        let (resp, _) = self.get_json(&uri).await?;
+        Ok(match resp.get("status") {
+            Some(json) => json.to_string().trim().to_owned().into(),
+            None => JobScope::Invalid,
+        })
     }
 }
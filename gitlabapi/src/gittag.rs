@@ -0,0 +1,29 @@
+use chrono::{DateTime, Utc};
+
+use crate::prelude::*;
+
+/// A git tag of a project.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitTag {
+    pub name: String,
+    /// Creation of an annotated tag, or date of the tagged commit
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl GitTag {
+    pub fn from_json(json: &Value) -> Option<Self> {
+        let date = |value: &Value| {
+            value
+                .as_str()
+                .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+                .map(|date| date.with_timezone(&Utc))
+        };
+
+        Some(GitTag {
+            name: json["name"].as_str()?.to_owned(),
+            created_at: date(&json["created_at"])
+                .or_else(|| date(&json["commit"]["committed_date"]))
+                .or_else(|| date(&json["commit"]["created_at"])),
+        })
+    }
+}
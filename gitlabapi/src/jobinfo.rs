@@ -53,6 +53,12 @@ impl From<String> for JobScope {
 pub struct JobInfo {
     /// ID of the JOB
     pub id: Option<u64>,
+    /// Job name, as in the pipeline definition
+    pub name: Option<String>,
+    /// Pipeline stage of the job
+    pub stage: Option<String>,
+    /// Environment the job deploys to
+    pub environment: Option<String>,
     /// Job Status
     pub status: Option<JobScope>,
     /// URL to access jog page
@@ -86,6 +92,8 @@ impl JobInfo {
         let git_tag = self.git_tag.as_ref().unwrap_or(&default_string);
         let url = self.url.as_ref().unwrap_or(&default_string);
         let job_id = self.id.unwrap_or(0);
+        let job_name = self.name.as_ref().unwrap_or(&default_string);
+        let environment = self.environment.as_ref().unwrap_or(&default_string);
         // let status = self.status.unwrap_or(JobScope::Invalid);
         let status = match self.status {
             Some(status) => match status {
@@ -121,6 +129,10 @@ impl JobInfo {
                 </tr><tr>
                 <td>Job id:</td><td><b>{job_id}</b></td>
                 </tr><tr>
+                <td>Job name:</td><td><b>{job_name}</b></td>
+                </tr><tr>
+                <td>Environment:</td><td><b>{environment}</b></td>
+                </tr><tr>
                 <td>Job status:</td><td><b>{status}</b></td>
                 </tr>
             </table>
@@ -129,6 +141,57 @@ impl JobInfo {
             proj_name.to_uppercase()
         )
     }
+
+    /// Plain text rendering of the same fields as `to_html`, for text-only mail clients.
+    pub fn to_text(&self) -> String {
+        let unknown = "unknown";
+
+        let proj_name = self.proj_name.as_deref().unwrap_or(unknown);
+        let status = self.status.unwrap_or(JobScope::Invalid);
+        let fields = [
+            ("Project name", proj_name.to_owned()),
+            (
+                "Git tag",
+                self.git_tag.as_deref().unwrap_or(unknown).to_owned(),
+            ),
+            (
+                "Branch",
+                self.branch.as_deref().unwrap_or(unknown).to_owned(),
+            ),
+            ("Source project id", self.source_id.unwrap_or(0).to_string()),
+            ("Deploy project id", self.proj_id.unwrap_or(0).to_string()),
+            (
+                "Deploy pipeline id",
+                self.pipeline_id.unwrap_or(0).to_string(),
+            ),
+            (
+                "User mail",
+                self.user_mail.as_deref().unwrap_or(unknown).to_owned(),
+            ),
+            ("Job URL", self.url.as_deref().unwrap_or(unknown).to_owned()),
+            ("Job id", self.id.unwrap_or(0).to_string()),
+            (
+                "Job name",
+                self.name.as_deref().unwrap_or(unknown).to_owned(),
+            ),
+            (
+                "Environment",
+                self.environment.as_deref().unwrap_or(unknown).to_owned(),
+            ),
+            ("Job status", status.to_string()),
+        ];
+
+        let lines = fields
+            .iter()
+            .map(|(label, value)| format!("{:<20}{value}", format!("{label}:")))
+            .collect::<Vec<_>>();
+
+        format!(
+            "{}: {status}\n\n{}\n",
+            proj_name.to_uppercase(),
+            lines.join("\n")
+        )
+    }
 }
 
 impl Display for JobInfo {
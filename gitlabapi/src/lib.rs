@@ -1,33 +1,53 @@
 // use async_trait::async_trait;
 
+mod error;
 mod getters;
 mod getters_traits;
+mod gittag;
 mod jobinfo;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+mod observer;
+mod paginator;
+mod retry;
 pub mod setters;
 mod tests;
 mod utils;
 
+use std::sync::{Arc, OnceLock};
+
 pub use configloader::Config;
+pub use error::Error;
+pub use observer::{endpoint, RequestObserver};
 
 /// Specify how many concurrent tasks
 pub const STREAM_BUFF_SIZE: usize = 15;
 
 pub mod prelude {
     pub use super::getters_traits::*;
+    pub use super::gittag::GitTag;
     pub use super::jobinfo::{JobInfo, JobScope};
+    pub use super::observer::RequestObserver;
+    pub use super::paginator::Pagination;
     pub use super::setters;
     pub use super::setters::JobActions;
     pub use super::Config;
+    pub use super::Error;
     pub use super::GitlabJOB;
     pub use super::STREAM_BUFF_SIZE;
     pub use super::{GroupID, JobID, PipelineID, ProjectID};
     pub use log::{debug, error, warn};
+    pub use reqwest::StatusCode;
     pub use serde_json::Value;
 }
 
 /// API caller configured from `Config` module.
 pub struct GitlabJOB {
     pub config: Config,
+    /// Shared by every request, so connections are pooled, built on the first call
+    client: OnceLock<reqwest::Client>,
+    /// Told about every request sent
+    observer: Option<Arc<dyn RequestObserver>>,
 }
 
 type ID = u64;
@@ -36,8 +56,16 @@ impl GitlabJOB {
     pub fn new(config: &Config) -> Self {
         GitlabJOB {
             config: config.clone(),
+            client: OnceLock::new(),
+            observer: None,
         }
     }
+
+    /// Report every request sent to `observer`.
+    pub fn with_observer(mut self, observer: Arc<dyn RequestObserver>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -0,0 +1,620 @@
+//! In-process fake Gitlab answering the API calls made by this crate, so tests run offline.
+//!
+//! Enabled for this crate tests, and for other crates tests through the `mock` feature.
+
+use core::convert::Infallible;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use chrono::{DateTime, Utc};
+use hyper::service::service_fn;
+use hyper::{Body, Request, Response};
+pub use hyper::{Method, StatusCode};
+use serde_json::json;
+
+use crate::prelude::*;
+
+/// Token the fake Gitlab accepts.
+pub const MOCK_TOKEN: &str = "mock-token";
+
+/// Gitlab default page size.
+const DEFAULT_PER_PAGE: usize = 20;
+
+/// A job served by the fake Gitlab.
+#[derive(Debug, Clone)]
+pub struct MockJob {
+    pub id: u64,
+    pub pipeline_id: u64,
+    pub name: String,
+    pub stage: String,
+    pub environment: Option<String>,
+    pub status: JobScope,
+    /// Status taken when the job is played
+    pub after_play: JobScope,
+    /// Branch or tag the pipeline ran for
+    pub git_ref: String,
+    pub committer_email: Option<String>,
+}
+
+impl MockJob {
+    /// A manual job ending successfully once played.
+    pub fn manual(id: u64, pipeline_id: u64) -> Self {
+        MockJob {
+            id,
+            pipeline_id,
+            name: "deploy".to_owned(),
+            stage: "deploy".to_owned(),
+            environment: None,
+            status: JobScope::Manual,
+            after_play: JobScope::Success,
+            git_ref: "main".to_owned(),
+            committer_email: None,
+        }
+    }
+
+    fn to_json(&self, base_url: &str, project: u64) -> Value {
+        json!({
+            "id": self.id,
+            "name": self.name,
+            "stage": self.stage,
+            "environment": self.environment.as_ref().map(|name| json!({"name": name})),
+            "status": self.status.to_string(),
+            "ref": self.git_ref,
+            "web_url": format!("{base_url}/projects/{project}/-/jobs/{}", self.id),
+            "pipeline": { "id": self.pipeline_id },
+            "commit": {
+                "committer_email": self.committer_email,
+                "ref_name": self.git_ref,
+            },
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockProject {
+    name: String,
+    protected_tags: Vec<String>,
+    /// Tag names and their creation dates
+    tags: Vec<(String, DateTime<Utc>)>,
+    jobs: BTreeMap<u64, MockJob>,
+    pipeline_vars: BTreeMap<u64, Vec<(String, String)>>,
+}
+
+/// An unsuccessful answer given instead of the real one.
+#[derive(Debug)]
+struct Failure {
+    method: Method,
+    path: String,
+    status: StatusCode,
+    remaining: usize,
+}
+
+#[derive(Debug, Default)]
+struct MockGroup {
+    projects: Vec<u64>,
+    subgroups: Vec<u64>,
+    shared: Vec<u64>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    groups: BTreeMap<u64, MockGroup>,
+    projects: BTreeMap<u64, MockProject>,
+    failures: Vec<Failure>,
+    requests: Vec<String>,
+    hide_totals: bool,
+    hide_links: bool,
+}
+
+/// Fake Gitlab listening on a local port until dropped.
+pub struct MockGitlab {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    server: tokio::task::JoinHandle<()>,
+}
+
+impl MockGitlab {
+    /// Start listening on a random local port.
+    pub async fn start() -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Couldn't bind the fake Gitlab");
+        let addr = listener
+            .local_addr()
+            .expect("Couldn't get the fake Gitlab address");
+        let state = Arc::new(Mutex::new(State::default()));
+        let base_url = format!("http://{addr}");
+
+        let shared = Arc::clone(&state);
+        let server = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let state = Arc::clone(&shared);
+                let base_url = base_url.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |request| {
+                        let response = answer(&state, &base_url, &request);
+                        async move { Ok::<_, Infallible>(response) }
+                    });
+                    if let Err(error) = hyper::server::conn::Http::new()
+                        .serve_connection(stream, service)
+                        .await
+                    {
+                        debug!("Fake Gitlab connection closed: {error}");
+                    }
+                });
+            }
+        });
+
+        MockGitlab {
+            addr,
+            state,
+            server,
+        }
+    }
+
+    /// Configuration pointing to this server, retrying without long waits.
+    pub fn config(&self) -> Config {
+        Config {
+            base_url: Some(self.base_url()),
+            private_token: Some(MOCK_TOKEN.to_owned()),
+            retry: Some(configloader::RetryConfig {
+                attempts: Some(3),
+                base_delay: Some(1),
+                max_delay: Some(10),
+            }),
+            ..Default::default()
+        }
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn add_group(&self, id: u64, projects: &[u64]) {
+        self.state().groups.entry(id).or_default().projects = projects.to_vec();
+    }
+
+    pub fn add_subgroup(&self, parent: u64, id: u64, projects: &[u64]) {
+        self.add_group(id, projects);
+        self.state()
+            .groups
+            .entry(parent)
+            .or_default()
+            .subgroups
+            .push(id);
+    }
+
+    /// Share a project with a group other than its own.
+    pub fn share_project(&self, group: u64, project: u64) {
+        self.state()
+            .groups
+            .entry(group)
+            .or_default()
+            .shared
+            .push(project);
+    }
+
+    pub fn add_project(&self, id: u64, name: &str) {
+        self.state().projects.entry(id).or_default().name = name.to_owned();
+    }
+
+    pub fn add_tags(&self, project: u64, tags: &[&str]) {
+        self.state()
+            .projects
+            .entry(project)
+            .or_default()
+            .tags
+            .extend(tags.iter().map(|&tag| (tag.to_owned(), Utc::now())));
+    }
+
+    /// Protect tags matching a name, which may hold `*` wildcards.
+    pub fn protect_tag(&self, project: u64, name: &str) {
+        self.state()
+            .projects
+            .entry(project)
+            .or_default()
+            .protected_tags
+            .push(name.to_owned());
+    }
+
+    pub fn add_tag_at(&self, project: u64, tag: &str, created_at: DateTime<Utc>) {
+        self.state()
+            .projects
+            .entry(project)
+            .or_default()
+            .tags
+            .push((tag.to_owned(), created_at));
+    }
+
+    pub fn add_job(&self, project: u64, job: MockJob) {
+        self.state()
+            .projects
+            .entry(project)
+            .or_default()
+            .jobs
+            .insert(job.id, job);
+    }
+
+    pub fn add_pipeline_vars(&self, project: u64, pipeline: u64, vars: &[(&str, &str)]) {
+        self.state()
+            .projects
+            .entry(project)
+            .or_default()
+            .pipeline_vars
+            .entry(pipeline)
+            .or_default()
+            .extend(
+                vars.iter()
+                    .map(|&(key, value)| (key.to_owned(), value.to_owned())),
+            );
+    }
+
+    /// Answer `status` to the next `times` requests whose path starts with `path`.
+    pub fn fail(&self, method: Method, path: &str, status: StatusCode, times: usize) {
+        self.state().failures.push(Failure {
+            method,
+            path: path.to_owned(),
+            status,
+            remaining: times,
+        });
+    }
+
+    /// Leave out `x-total` and `x-total-pages` headers, as Gitlab does for big lists.
+    pub fn hide_totals(&self) {
+        self.state().hide_totals = true;
+    }
+
+    /// Leave out `Link` headers on numbered pages.
+    pub fn hide_links(&self) {
+        self.state().hide_links = true;
+    }
+
+    /// Current status of a job.
+    pub fn job_status(&self, project: u64, job: u64) -> Option<JobScope> {
+        self.state()
+            .projects
+            .get(&project)
+            .and_then(|proj| proj.jobs.get(&job))
+            .map(|job| job.status)
+    }
+
+    /// Every request received, as `METHOD /path`.
+    pub fn requests(&self) -> Vec<String> {
+        self.state().requests.clone()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("Fake Gitlab state poisoned")
+    }
+}
+
+impl Drop for MockGitlab {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+fn tag_json((name, created_at): &(String, DateTime<Utc>)) -> Value {
+    json!({
+        "name": name,
+        "created_at": null,
+        "commit": { "committed_date": created_at.to_rfc3339() },
+    })
+}
+
+fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while let Some(&byte) = bytes.get(index) {
+        let escaped = (byte == b'%')
+            .then(|| segment.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(escaped) => {
+                decoded.push(escaped);
+                index += 3;
+            }
+            None => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn json_response(status: StatusCode, body: &Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(body.to_string()));
+    *response.status_mut() = status;
+    response
+}
+
+fn not_found() -> Response<Body> {
+    json_response(StatusCode::NOT_FOUND, &json!({"message": "404 Not Found"}))
+}
+
+/// A list request, as needed to link its other pages.
+struct Listing<'req> {
+    /// Address without query
+    url: String,
+    query: &'req BTreeMap<String, String>,
+    hide_totals: bool,
+    hide_links: bool,
+}
+
+impl Listing<'_> {
+    /// `Link` header entry to the same list with a changed parameter.
+    fn link(&self, param: &str, value: impl ToString, rel: &str) -> String {
+        let mut query = self.query.clone();
+        query.insert(param.to_owned(), value.to_string());
+        let query = query
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>()
+            .join("&");
+        format!("<{}?{query}>; rel=\"{rel}\"", self.url)
+    }
+}
+
+/// Answer a page of `items`, with Gitlab pagination headers.
+fn paginated(items: Vec<Value>, listing: &Listing<'_>) -> Response<Body> {
+    let param = |name: &str| {
+        listing
+            .query
+            .get(name)
+            .and_then(|value| value.parse::<usize>().ok())
+            .filter(|&value| value > 0)
+    };
+    let per_page = param("per_page").unwrap_or(DEFAULT_PER_PAGE);
+
+    if listing.query.get("pagination").map(String::as_str) == Some("keyset") {
+        let after = param("id_after").unwrap_or_default();
+        let mut rest = items
+            .into_iter()
+            .filter(|item| {
+                item["id"]
+                    .as_u64()
+                    .and_then(|id| usize::try_from(id).ok())
+                    .is_some_and(|id| id > after)
+            })
+            .collect::<Vec<_>>();
+        rest.sort_by_key(|item| item["id"].as_u64());
+        let more = rest.len() > per_page;
+        rest.truncate(per_page);
+
+        let last = rest.last().and_then(|item| item["id"].as_u64());
+        let mut response = json_response(StatusCode::OK, &Value::Array(rest));
+        if let Some(last) = last.filter(|_| more) {
+            if let Ok(link) = listing.link("id_after", last, "next").parse() {
+                response.headers_mut().insert("link", link);
+            }
+        }
+        return response;
+    }
+
+    let page = param("page").unwrap_or(1);
+    let total = items.len();
+    let total_pages = total.div_ceil(per_page).max(1);
+
+    let page_items = items
+        .into_iter()
+        .skip((page - 1) * per_page)
+        .take(per_page)
+        .collect::<Vec<_>>();
+
+    let mut response = json_response(StatusCode::OK, &Value::Array(page_items));
+    let headers = response.headers_mut();
+    headers.insert("x-page", page.into());
+    headers.insert("x-per-page", per_page.into());
+    let next_page = if page < total_pages {
+        (page + 1).to_string()
+    } else {
+        String::new()
+    };
+    if let Ok(next_page) = next_page.parse() {
+        headers.insert("x-next-page", next_page);
+    }
+    if !listing.hide_totals {
+        headers.insert("x-total", total.into());
+        headers.insert("x-total-pages", total_pages.into());
+    }
+    if !listing.hide_links {
+        let mut links = vec![listing.link("page", 1, "first")];
+        if page < total_pages {
+            links.push(listing.link("page", page + 1, "next"));
+        }
+        if !listing.hide_totals {
+            links.push(listing.link("page", total_pages, "last"));
+        }
+        if let Ok(links) = links.join(", ").parse() {
+            headers.insert("link", links);
+        }
+    }
+    response
+}
+
+fn answer(state: &Mutex<State>, base_url: &str, request: &Request<Body>) -> Response<Body> {
+    let Ok(mut state) = state.lock() else {
+        return json_response(StatusCode::INTERNAL_SERVER_ERROR, &json!({}));
+    };
+    let method = request.method().clone();
+    let path = request.uri().path().to_owned();
+    let query = request
+        .uri()
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.to_owned(), value.to_owned()))
+        .collect::<BTreeMap<_, _>>();
+
+    state.requests.push(format!("{method} {path}"));
+    let listing = Listing {
+        url: format!("{base_url}{path}"),
+        query: &query,
+        hide_totals: state.hide_totals,
+        hide_links: state.hide_links,
+    };
+
+    if request
+        .headers()
+        .get("PRIVATE-TOKEN")
+        .is_none_or(|token| token != MOCK_TOKEN)
+    {
+        return json_response(
+            StatusCode::UNAUTHORIZED,
+            &json!({"message": "401 Unauthorized"}),
+        );
+    }
+
+    if let Some(failure) = state.failures.iter_mut().find(|failure| {
+        failure.remaining > 0 && failure.method == method && path.starts_with(&failure.path)
+    }) {
+        failure.remaining -= 1;
+        let mut response = json_response(failure.status, &json!({"message": "injected failure"}));
+        if failure.status == StatusCode::TOO_MANY_REQUESTS {
+            response.headers_mut().insert("retry-after", 0.into());
+        }
+        return response;
+    }
+
+    let segments = path
+        .trim_start_matches("/api/v4/")
+        .split('/')
+        .collect::<Vec<_>>();
+    let id = |index: usize| segments.get(index).and_then(|id| id.parse::<u64>().ok());
+
+    match (&method, segments.as_slice()) {
+        (&Method::GET, ["groups", _, "projects"]) => {
+            let Some(group) = id(1).and_then(|group| state.groups.get(&group)) else {
+                return not_found();
+            };
+            let with_shared = query
+                .get("with_shared")
+                .is_none_or(|shared| shared == "true");
+            let items = group
+                .projects
+                .iter()
+                .chain(group.shared.iter().filter(|_| with_shared))
+                .map(|id| {
+                    let name = state.projects.get(id).map(|proj| proj.name.clone());
+                    json!({"id": id, "name": name})
+                })
+                .collect();
+            paginated(items, &listing)
+        }
+        (&Method::GET, ["groups", _, "subgroups"]) => {
+            let Some(group) = id(1).and_then(|group| state.groups.get(&group)) else {
+                return not_found();
+            };
+            let items = group.subgroups.iter().map(|id| json!({"id": id})).collect();
+            paginated(items, &listing)
+        }
+        (&Method::GET, ["projects"]) => {
+            let items = state
+                .projects
+                .iter()
+                .map(|(id, proj)| json!({"id": id, "name": proj.name}))
+                .collect();
+            paginated(items, &listing)
+        }
+        (&Method::GET, ["projects", _]) => match id(1).and_then(|id| state.projects.get(&id)) {
+            Some(proj) => json_response(StatusCode::OK, &json!({"id": id(1), "name": proj.name})),
+            None => not_found(),
+        },
+        (&Method::GET, ["projects", _, "jobs"]) => {
+            let Some(proj) = id(1).and_then(|id| state.projects.get(&id)) else {
+                return not_found();
+            };
+            let scope = query
+                .get("scope")
+                .map(|scope| JobScope::from(scope.clone()));
+            let items = proj
+                .jobs
+                .values()
+                .filter(|job| scope.is_none_or(|scope| job.status == scope))
+                .map(|job| job.to_json(base_url, id(1).unwrap_or_default()))
+                .collect();
+            paginated(items, &listing)
+        }
+        (&Method::GET, ["projects", _, "jobs", _]) => {
+            match id(1)
+                .and_then(|proj| state.projects.get(&proj))
+                .and_then(|proj| proj.jobs.get(&id(3)?))
+            {
+                Some(job) => json_response(
+                    StatusCode::OK,
+                    &job.to_json(base_url, id(1).unwrap_or_default()),
+                ),
+                None => not_found(),
+            }
+        }
+        (&Method::POST, ["projects", _, "jobs", _, action @ ("play" | "cancel")]) => {
+            let Some(job) = id(1)
+                .and_then(|proj| state.projects.get_mut(&proj))
+                .and_then(|proj| proj.jobs.get_mut(&id(3)?))
+            else {
+                return not_found();
+            };
+            // Like Gitlab, a played job is answered with OK and a canceled one with CREATED
+            let status = if *action == "play" {
+                if job.status != JobScope::Manual {
+                    return json_response(
+                        StatusCode::BAD_REQUEST,
+                        &json!({"message": "400 Unplayable Job"}),
+                    );
+                }
+                job.status = job.after_play;
+                StatusCode::OK
+            } else {
+                job.status = JobScope::Canceled;
+                StatusCode::CREATED
+            };
+            json_response(status, &job.to_json(base_url, id(1).unwrap_or_default()))
+        }
+        (&Method::GET, ["projects", _, "pipelines", _, "variables"]) => {
+            let Some(proj) = id(1).and_then(|id| state.projects.get(&id)) else {
+                return not_found();
+            };
+            let items = id(3)
+                .and_then(|pipeline| proj.pipeline_vars.get(&pipeline))
+                .into_iter()
+                .flatten()
+                .map(|(key, value)| json!({"key": key, "value": value, "variable_type": "env_var"}))
+                .collect();
+            paginated(items, &listing)
+        }
+        (&Method::GET, ["projects", _, "repository", "tags"]) => {
+            let Some(proj) = id(1).and_then(|id| state.projects.get(&id)) else {
+                return not_found();
+            };
+            let items = proj.tags.iter().map(tag_json).collect();
+            paginated(items, &listing)
+        }
+        (&Method::GET, ["projects", _, "protected_tags"]) => {
+            let Some(proj) = id(1).and_then(|id| state.projects.get(&id)) else {
+                return not_found();
+            };
+            let items = proj
+                .protected_tags
+                .iter()
+                .map(|name| json!({"name": name, "create_access_levels": []}))
+                .collect();
+            paginated(items, &listing)
+        }
+        (&Method::GET, ["projects", _, "repository", "tags", name]) => {
+            let name = percent_decode(name);
+            match id(1)
+                .and_then(|id| state.projects.get(&id))
+                .and_then(|proj| proj.tags.iter().find(|(tag, _)| tag == &name))
+            {
+                Some(tag) => json_response(StatusCode::OK, &tag_json(tag)),
+                None => not_found(),
+            }
+        }
+        _ => not_found(),
+    }
+}
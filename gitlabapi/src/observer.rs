@@ -0,0 +1,28 @@
+use core::time::Duration;
+
+/// Told about every call to the Gitlab API, like a metrics collector.
+pub trait RequestObserver: Send + Sync {
+    /// A call to `endpoint` ended after `elapsed`, with its answer status, `None` when Gitlab
+    /// didn't answer.
+    fn observe(&self, method: &str, endpoint: &str, status: Option<u16>, elapsed: Duration);
+}
+
+/// Path of an API url with ids and tag names replaced by placeholders, so every call to a same
+/// endpoint is reported alike.
+pub fn endpoint(path: &str) -> String {
+    let mut previous = "";
+    path.split('/')
+        .map(|segment| {
+            let normalized = if !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit()) {
+                ":id"
+            } else if previous == "tags" {
+                ":name"
+            } else {
+                segment
+            };
+            previous = segment;
+            normalized
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,117 @@
+use reqwest::header::HeaderMap;
+use reqwest::Url;
+
+use crate::prelude::*;
+
+/// Items asked for each page, the most Gitlab allows.
+const PER_PAGE: u32 = 100;
+
+/// How Gitlab splits a list in pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pagination {
+    /// Numbered pages
+    Offset,
+    /// Pages starting after the last seen item, faster on big lists.
+    /// The path must set the `order_by` Gitlab supports for the listed resource.
+    Keyset,
+}
+
+impl GitlabJOB {
+    /// Every item of a list endpoint, following the pages Gitlab links to.
+    pub async fn get_all(&self, path: &str, pagination: Pagination) -> Result<Vec<Value>, Error> {
+        let separator = if path.contains('?') { '&' } else { '?' };
+        let mut first = format!("{path}{separator}per_page={PER_PAGE}");
+        if pagination == Pagination::Keyset {
+            first.push_str("&pagination=keyset");
+        }
+
+        let mut items = vec![];
+        let mut next = Some(self.gen_url(&first)?);
+
+        while let Some(url) = next.take() {
+            debug!("Getting json from: {url}");
+            let response = self
+                .send_request(self.client()?.get(url.clone()), true)
+                .await?;
+            let headers = response.headers().clone();
+            let text = self.response_text(response).await?;
+
+            match Self::parse_json(text)? {
+                Value::Array(page) => items.extend(page),
+                other => warn!("Expected a list from {url}, got: {other}"),
+            }
+
+            next = next_page(&url, &headers, pagination)?.filter(|next| next != &url);
+        }
+
+        Ok(items)
+    }
+}
+
+/// Address of the page after `current`, if any.
+///
+/// Gitlab links to it with a `Link: <...>; rel="next"` header, and numbered pages also have
+/// an `x-next-page` header, left empty in the last one.
+pub(crate) fn next_page(
+    current: &Url,
+    headers: &HeaderMap,
+    pagination: Pagination,
+) -> Result<Option<Url>, Error> {
+    if let Some(link) = next_link(headers) {
+        let link = Url::parse(link).map_err(|error| Error::InvalidUrl {
+            url: link.to_owned(),
+            reason: error.to_string(),
+        })?;
+
+        // Only path and query are taken, so the token is never sent to another host
+        let mut next = current.clone();
+        next.set_path(link.path());
+        next.set_query(link.query());
+        return Ok(Some(next));
+    }
+
+    if pagination == Pagination::Offset {
+        let next_num = headers
+            .get("x-next-page")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok());
+
+        if let Some(next_num) = next_num {
+            let query = current
+                .query_pairs()
+                .filter(|(key, _)| key != "page")
+                .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                .collect::<Vec<_>>();
+
+            let mut next = current.clone();
+            next.query_pairs_mut()
+                .clear()
+                .extend_pairs(query)
+                .append_pair("page", &next_num.to_string());
+            return Ok(Some(next));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Target of the `rel="next"` entry of a `Link` header.
+fn next_link(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all("link")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|entry| {
+            let (target, params) = entry.trim().split_once(';')?;
+            params
+                .split(';')
+                .any(|param| {
+                    param
+                        .trim()
+                        .strip_prefix("rel=")
+                        .is_some_and(|rel| rel.trim_matches('"') == "next")
+                })
+                .then(|| target.trim().trim_start_matches('<').trim_end_matches('>'))
+        })
+}
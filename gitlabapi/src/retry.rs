@@ -0,0 +1,154 @@
+use core::hash::{BuildHasher as _, Hasher as _};
+use core::time::Duration;
+use std::collections::hash_map::RandomState;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+
+use crate::endpoint;
+use crate::prelude::*;
+use configloader::RetryConfig;
+
+/// Statuses worth another try, as Gitlab or its proxies may answer them while overloaded.
+const RETRY_STATUS: [StatusCode; 6] = [
+    StatusCode::REQUEST_TIMEOUT,
+    StatusCode::TOO_MANY_REQUESTS,
+    StatusCode::INTERNAL_SERVER_ERROR,
+    StatusCode::BAD_GATEWAY,
+    StatusCode::SERVICE_UNAVAILABLE,
+    StatusCode::GATEWAY_TIMEOUT,
+];
+
+/// Header values from here on are unix timestamps rather than delay seconds.
+const TIMESTAMP_FLOOR: u64 = 1_000_000_000;
+
+impl GitlabJOB {
+    /// Send a request following the configured retry policy.
+    ///
+    /// Idempotent requests are retried on any transport error and on transient statuses, the
+    /// others only when the connection couldn't be established, so Gitlab never saw them.
+    pub async fn send_request(
+        &self,
+        request: reqwest::RequestBuilder,
+        idempotent: bool,
+    ) -> Result<reqwest::Response, Error> {
+        let policy = self.config.retry.clone().unwrap_or_default();
+        // Requests are only built twice when someone observes them
+        let target = self.observer.as_ref().and_then(|_| {
+            request
+                .try_clone()
+                .and_then(|copy| copy.build().ok())
+                .map(|built| (built.method().to_string(), endpoint(built.url().path())))
+        });
+        let mut attempt = 1;
+
+        loop {
+            let started = Instant::now();
+            // Requests with streamed bodies can't be cloned, neither retried
+            let Some(current) = request.try_clone() else {
+                let result = self.read_timeout(request.send()).await;
+                self.observe(target.as_ref(), &result, started);
+                return result;
+            };
+
+            let result = self.read_timeout(current.send()).await;
+            self.observe(target.as_ref(), &result, started);
+            let (error, wait) = match result {
+                Ok(response) if idempotent && RETRY_STATUS.contains(&response.status()) => {
+                    let status = response.status();
+                    let wait = retry_after(response.headers());
+                    let body = response.text().await.unwrap_or_default();
+                    (Error::from_status(status, body), wait)
+                }
+                Ok(response) => return Ok(response),
+                Err(Error::Transport(error))
+                    if error.is_connect() || (idempotent && !error.is_builder()) =>
+                {
+                    (Error::Transport(error), None)
+                }
+                Err(error @ Error::Timeout(_)) if idempotent => (error, None),
+                Err(error) => return Err(error),
+            };
+
+            if attempt >= policy.attempts() {
+                return Err(error);
+            }
+
+            let delay = wait
+                .unwrap_or_else(|| backoff(&policy, attempt))
+                .min(policy.max_delay());
+            warn!(
+                "Gitlab call failed, try {attempt} of {}, retrying in {delay:?}: {error}",
+                policy.attempts()
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Tell the observer, if any, how a request went.
+    fn observe(
+        &self,
+        target: Option<&(String, String)>,
+        result: &Result<reqwest::Response, Error>,
+        started: Instant,
+    ) {
+        let (Some(observer), Some((method, endpoint))) = (self.observer.as_ref(), target) else {
+            return;
+        };
+        let status = match result {
+            Ok(response) => Some(response.status()),
+            Err(error) => error.status(),
+        };
+        observer.observe(
+            method,
+            endpoint,
+            status.map(|status| status.as_u16()),
+            started.elapsed(),
+        );
+    }
+}
+
+/// Exponential delay before a new try, with a random jitter of up to its half.
+pub(crate) fn backoff(policy: &RetryConfig, attempt: u32) -> Duration {
+    let delay = policy
+        .base_delay()
+        .saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(policy.max_delay());
+
+    let half = delay / 2;
+    let jitter = u64::try_from(half.as_millis())
+        .ok()
+        .filter(|&millis| millis > 0)
+        .map_or(0, |millis| {
+            RandomState::new().build_hasher().finish() % (millis + 1)
+        });
+
+    half + Duration::from_millis(jitter)
+}
+
+/// Delay asked by Gitlab through the `Retry-After` or `RateLimit-Reset` headers.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+
+    if let Some(value) = header("retry-after") {
+        // Either delay seconds or a http date
+        return match value.trim().parse::<u64>() {
+            Ok(seconds) => Some(Duration::from_secs(seconds)),
+            Err(_) => chrono::DateTime::parse_from_rfc2822(value)
+                .ok()
+                .and_then(|date| u64::try_from(date.timestamp()).ok())
+                .map(|timestamp| Duration::from_secs(timestamp).saturating_sub(now)),
+        };
+    }
+
+    // Gitlab sends the reset time as an unix timestamp, other servers as delay seconds
+    let reset = header("ratelimit-reset")?.trim().parse::<u64>().ok()?;
+    Some(if reset >= TIMESTAMP_FLOOR {
+        Duration::from_secs(reset).saturating_sub(now)
+    } else {
+        Duration::from_secs(reset)
+    })
+}
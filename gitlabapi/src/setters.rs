@@ -3,26 +3,24 @@ use async_trait::async_trait;
 use crate::prelude::*;
 
 impl GitlabJOB {
-    /// Post facilitator with serde_json::Value as post body
-    pub async fn post_json(&self, url: String, json: Value) -> Result<Value, String> {
-        let resp = self.api_post(url.as_str(), json);
-
-        match resp.send().await {
-            Err(e) => Err(format!("Error while posting to {url}: {e}")),
-            Ok(response) => {
-                debug!("HTTP Response Headers: {:?}", response.headers());
-                debug!("HTTP Response Status: {:?}", response.status());
-                debug!("HTTP Response Url: {:?}", response.url());
-                match response.text().await {
-                    Err(e) => Err(e.to_string()),
-                    Ok(text) => Self::parse_json(text),
-                }
-            }
-        }
+    /// Post facilitator with serde_json::Value as post body, returning the answered status
+    /// with the body.
+    pub async fn post_json(&self, url: String, json: Value) -> Result<(StatusCode, Value), Error> {
+        let resp = self.api_post(url.as_str(), json)?;
+
+        let response = self.send_request(resp, false).await?;
+        debug!("HTTP Response Headers: {:?}", response.headers());
+        debug!("HTTP Response Status: {:?}", response.status());
+        debug!("HTTP Response Url: {:?}", response.url());
+
+        let status = response.status();
+        let text = self.response_text(response).await?;
+        Ok((status, Self::parse_json(text)?))
     }
 }
 
-type ApiResult<'j> = Result<&'j JobInfo, JobInfo>;
+/// The acted job with the status Gitlab answered with.
+type ApiResult<'j> = Result<(&'j JobInfo, StatusCode), Error>;
 
 #[async_trait]
 pub trait JobActions<'a> {
@@ -33,38 +31,16 @@ pub trait JobActions<'a> {
 #[async_trait]
 impl<'a> JobActions<'a> for GitlabJOB {
     async fn cancel_job(&self, job: &'a JobInfo) -> ApiResult<'a> {
-        let url = format!(
-            "api/v4/projects/{}/jobs/{}/cancel",
-            job.proj_id.unwrap(),
-            job.id.unwrap()
-        );
+        let url = format!("{}/cancel", Self::job_path(job)?);
 
-        match self.post_json(url, Value::String("".to_owned())).await {
-            Ok(_) => Ok(job),
-            Err(e) => {
-                let mut job = job.clone();
-                job.status = Some(JobScope::Invalid);
-                error!("Error to cancel job {job}: {}", e);
-                Err(job)
-            }
-        }
+        let (status, _) = self.post_json(url, Value::String("".to_owned())).await?;
+        Ok((job, status))
     }
 
     async fn play_job(&self, job: &'a JobInfo) -> ApiResult<'a> {
-        let url = format!(
-            "api/v4/projects/{}/jobs/{}/play",
-            job.proj_id.unwrap(),
-            job.id.unwrap()
-        );
+        let url = format!("{}/play", Self::job_path(job)?);
 
-        match self.post_json(url, Value::String("".to_owned())).await {
-            Ok(_) => Ok(job),
-            Err(e) => {
-                let mut job = job.clone();
-                job.status = Some(JobScope::Invalid);
-                error!("Error to play job {job}: {}", e);
-                Err(job)
-            }
-        }
+        let (status, _) = self.post_json(url, Value::String("".to_owned())).await?;
+        Ok((job, status))
     }
 }
@@ -1,8 +1,9 @@
 #[cfg(test)]
 mod test_http {
 
+    use crate::mock::{Method, MockGitlab, MockJob, StatusCode};
     use crate::prelude::*;
-    use std::io::Write;
+    use std::collections::HashSet;
 
     fn init() {
         let _ = env_logger::builder()
@@ -11,257 +12,732 @@ mod test_http {
             .try_init();
     }
 
-    #[tokio::test]
-    async fn test_api_get() {
+    #[test]
+    fn test_errors() {
+        let api = GitlabJOB::new(&Config::default());
+
+        assert!(matches!(
+            api.gen_url("/api/v4/projects"),
+            Err(Error::Config("base_url"))
+        ));
+        assert!(matches!(
+            api.api_builder(),
+            Err(Error::Config("private_token"))
+        ));
+        assert!(matches!(
+            GitlabJOB::job_path(&JobInfo::default()),
+            Err(Error::IncompleteJob(_))
+        ));
+        assert!(matches!(
+            GitlabJOB::parse_json("<html></html>".to_owned()),
+            Err(Error::Json { .. })
+        ));
+        assert!(matches!(
+            Error::from_status(reqwest::StatusCode::UNAUTHORIZED, String::new()),
+            Error::Authentication { .. }
+        ));
+        assert!(matches!(
+            Error::from_status(reqwest::StatusCode::BAD_GATEWAY, String::new()),
+            Error::Status { .. }
+        ));
+        assert_eq!(
+            Error::from_status(reqwest::StatusCode::FORBIDDEN, String::new()).status(),
+            Some(reqwest::StatusCode::FORBIDDEN)
+        );
+        assert_eq!(Error::Config("base_url").status(), None);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = configloader::RetryConfig {
+            attempts: Some(5),
+            base_delay: Some(100),
+            max_delay: Some(1_000),
+        };
+
+        for (attempt, full) in [
+            (1, 100),
+            (2, 200),
+            (3, 400),
+            (4, 800),
+            (5, 1_000),
+            (40, 1_000),
+        ] {
+            let delay = crate::retry::backoff(&policy, attempt).as_millis();
+            assert!(
+                (full / 2..=full).contains(&delay),
+                "try {attempt} waited {delay}ms"
+            );
+        }
+    }
+
+    #[test]
+    fn test_retry_after() {
+        use core::time::Duration;
+        use reqwest::header::{HeaderMap, HeaderValue};
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(crate::retry::retry_after(&headers), None);
+
+        headers.insert("RateLimit-Reset", HeaderValue::from_static("7"));
+        assert_eq!(
+            crate::retry::retry_after(&headers),
+            Some(Duration::from_secs(7))
+        );
+
+        // An already elapsed reset timestamp
+        headers.insert("RateLimit-Reset", HeaderValue::from_static("1609844400"));
+        assert_eq!(crate::retry::retry_after(&headers), Some(Duration::ZERO));
+
+        // Retry-After takes precedence
+        headers.insert("Retry-After", HeaderValue::from_static("120"));
+        assert_eq!(
+            crate::retry::retry_after(&headers),
+            Some(Duration::from_secs(120))
+        );
+
+        headers.insert(
+            "Retry-After",
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(crate::retry::retry_after(&headers), Some(Duration::ZERO));
+    }
+
+    /// A group with two projects, the second one having manual jobs.
+    async fn gitlab() -> (MockGitlab, GitlabJOB) {
         init();
-        let config = Config::load_config().unwrap();
+        let gitlab = MockGitlab::start().await;
+        gitlab.add_group(1, &[10, 20]);
+        gitlab.add_project(10, "frontend");
+        gitlab.add_project(20, "backend");
+        gitlab.add_tags(20, &["v1.1.0", "v1.2.0"]);
+        gitlab.add_job(20, MockJob::manual(200, 2000));
+        gitlab.add_job(
+            20,
+            MockJob {
+                git_ref: "v1.2.0".to_owned(),
+                committer_email: Some("dev@test.tst".to_owned()),
+                environment: Some("production".to_owned()),
+                ..MockJob::manual(201, 2001)
+            },
+        );
+        gitlab.add_job(
+            20,
+            MockJob {
+                status: JobScope::Success,
+                ..MockJob::manual(150, 1500)
+            },
+        );
+        gitlab.add_pipeline_vars(
+            20,
+            2000,
+            &[("PROD_TAG", "v1.1.0"), ("trigger_email", "user@test.tst")],
+        );
+
+        let api = GitlabJOB::new(&gitlab.config());
+        (gitlab, api)
+    }
 
-        let api = GitlabJOB::new(&config);
+    #[tokio::test]
+    async fn test_api_get() {
+        let (_gitlab, api) = gitlab().await;
 
         let response = api
             .api_get("/api/v4/projects")
+            .unwrap()
             .send()
             .await
             .unwrap()
             .text()
-            .await;
-
-        let parsed_json: Value = serde_json::from_str(response.as_deref().unwrap()).unwrap();
-
-        parsed_json.as_array().iter().for_each(|projects| {
-            projects.iter().for_each(|proj| {
-                debug!("Project ID: {}", proj["id"]);
-                debug!("Project links: {}", proj["_links"]);
-            });
-        });
+            .await
+            .unwrap();
+        let parsed_json = GitlabJOB::parse_json(response).unwrap();
 
-        let _ = std::fs::File::create("/tmp/test.json")
+        let names = parsed_json
+            .as_array()
             .unwrap()
-            .write(response.unwrap().as_bytes());
+            .iter()
+            .map(|proj| proj["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["frontend", "backend"]);
     }
 
     #[tokio::test]
-    async fn test_get_group_projects() {
-        init();
+    async fn test_authentication() {
+        let (gitlab, _) = gitlab().await;
+        let mut config = gitlab.config();
+        config.private_token = Some("wrong".to_owned());
 
-        let config = Config::load_config().unwrap();
-
-        let gitlabjob = GitlabJOB::new(&config);
-        let groupid = gitlabjob.config.group_id.unwrap();
+        let api = GitlabJOB::new(&config);
+        assert!(matches!(
+            api.get_json("/api/v4/projects").await,
+            Err(Error::Authentication { .. })
+        ));
+    }
 
-        let response = gitlabjob.get_projs(GroupID(groupid)).await;
+    #[tokio::test]
+    async fn test_get_group_projects() {
+        let (gitlab, api) = gitlab().await;
+        gitlab.add_group(2, &(100..250).collect::<Vec<_>>());
+
+        let projects = api.get_projs(GroupID(2)).await.unwrap();
+        assert_eq!(projects, (100..250).collect());
+        // 100 projects per page
+        assert_eq!(
+            gitlab
+                .requests()
+                .iter()
+                .filter(|request| request.as_str() == "GET /api/v4/groups/2/projects")
+                .count(),
+            2
+        );
+
+        assert!(matches!(
+            api.get_projs(GroupID(3)).await,
+            Err(Error::Status { .. })
+        ));
+    }
 
-        response
-            .iter()
-            .for_each(|proj| debug!("Got project: {}", proj));
+    #[tokio::test]
+    async fn test_get_subgroups_projects() {
+        let (gitlab, mut api) = gitlab().await;
+        // 1 > 2 > 3 > 4, with 2 also shared with 1
+        gitlab.add_subgroup(1, 2, &[30]);
+        gitlab.add_subgroup(2, 3, &[40, 41]);
+        gitlab.add_subgroup(3, 4, &[50]);
+        gitlab.share_project(1, 30);
+        gitlab.share_project(1, 60);
+
+        let scan = |include_subgroups, max_depth, include_shared| {
+            let mut config = gitlab.config();
+            config.group = Some(configloader::GroupConfig {
+                include_subgroups: Some(include_subgroups),
+                max_depth,
+                include_shared: Some(include_shared),
+            });
+            GitlabJOB::new(&config)
+        };
+
+        let projects = api.get_projs(GroupID(1)).await.unwrap();
+        assert_eq!(projects, HashSet::from([10, 20, 30, 60]));
+
+        api = scan(false, None, false);
+        let projects = api.get_projs(GroupID(1)).await.unwrap();
+        assert_eq!(projects, HashSet::from([10, 20]));
+
+        api = scan(true, None, true);
+        let projects = api.get_projs(GroupID(1)).await.unwrap();
+        assert_eq!(projects, HashSet::from([10, 20, 30, 40, 41, 50, 60]));
+
+        api = scan(true, Some(2), false);
+        assert_eq!(
+            api.get_subgroups(GroupID(1), Some(2)).await.unwrap(),
+            [1, 2, 3]
+        );
+        let projects = api.get_projs(GroupID(1)).await.unwrap();
+        assert_eq!(projects, HashSet::from([10, 20, 30, 40, 41]));
     }
 
     #[tokio::test]
     async fn test_get_group_jobs() {
-        init();
+        let (_gitlab, api) = gitlab().await;
 
-        let config = Config::load_config().unwrap();
+        let response = api.get_jobs(GroupID(1), JobScope::Manual).await.unwrap();
 
-        let gitlabjob = GitlabJOB::new(&config);
-        let groupid = gitlabjob.config.group_id.unwrap();
-
-        let response = gitlabjob
-            .get_jobs(GroupID(groupid), JobScope::Canceled)
-            .await;
-
-        response
+        assert_eq!(response.keys().collect::<Vec<_>>(), [&ProjectID(20)]);
+        let mut ids = response[&ProjectID(20)]
             .iter()
-            .for_each(|proj| debug!("Got project: {:?}", proj));
+            .map(|job| job.id.unwrap())
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        assert_eq!(ids, [200, 201]);
     }
 
     #[tokio::test]
-    #[ignore = "specific group"]
-    async fn test_get_specifc_group_projects() {
-        init();
-
-        let mut config = Config::load_config().unwrap();
-        // config.group_id = Some(86);
-        config.group_id = Some(79566146);
-
-        let gitlabjob = GitlabJOB::new(&config);
-        let groupid = GroupID(config.group_id.unwrap());
-
-        let response = gitlabjob.get_projs(groupid);
-
-        let vec = response.await;
+    async fn test_get_prj_jobs() {
+        let (gitlab, api) = gitlab().await;
+        (300..430).for_each(|id| gitlab.add_job(20, MockJob::manual(id, id)));
 
-        vec.iter().for_each(|proj| debug!("Got project: {}", proj));
+        let response = api.get_jobs(ProjectID(20), JobScope::Manual).await.unwrap();
+        assert_eq!(response[&ProjectID(20)].len(), 132);
 
-        debug!("Got {} projects", &vec.len());
+        let success = api
+            .get_jobs(ProjectID(20), JobScope::Success)
+            .await
+            .unwrap();
+        assert_eq!(
+            success[&ProjectID(20)]
+                .iter()
+                .map(|job| job.id)
+                .collect::<Vec<_>>(),
+            [Some(150)]
+        );
+
+        assert!(api
+            .get_jobs(ProjectID(10), JobScope::Manual)
+            .await
+            .unwrap()
+            .is_empty());
     }
 
     #[tokio::test]
-    async fn test_get_prj_jobs() {
-        init();
-
-        let config = Config::load_config().unwrap();
-
-        let api = GitlabJOB::new(&config);
-        let proj = ProjectID(config.project_id.unwrap());
-
-        let response = api.get_jobs(proj, JobScope::Canceled);
+    async fn test_pagination_without_totals() {
+        let (gitlab, api) = gitlab().await;
+        (300..630).for_each(|id| gitlab.add_job(20, MockJob::manual(id, id)));
+        gitlab.add_group(2, &(100..350).collect::<Vec<_>>());
+
+        // Big lists have no totals, only links
+        gitlab.hide_totals();
+        let jobs = api.get_jobs(ProjectID(20), JobScope::Manual).await.unwrap();
+        assert_eq!(jobs[&ProjectID(20)].len(), 332);
+        assert_eq!(api.get_projs(GroupID(2)).await.unwrap().len(), 250);
+
+        // Numbered pages can still be followed without links
+        gitlab.hide_links();
+        let jobs = api.get_jobs(ProjectID(20), JobScope::Manual).await.unwrap();
+        assert_eq!(jobs[&ProjectID(20)].len(), 332);
+    }
 
-        response
-            .await
-            .iter()
-            .for_each(|job| debug!("Got: {:?}", job));
+    #[test]
+    fn test_next_page() {
+        use crate::paginator::next_page;
+        use reqwest::header::{HeaderMap, HeaderValue};
+
+        let current =
+            reqwest::Url::parse("https://gitlab.local/api/v4/projects/1/jobs?per_page=100&page=1")
+                .unwrap();
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            next_page(&current, &headers, Pagination::Offset).unwrap(),
+            None
+        );
+
+        headers.insert("x-next-page", HeaderValue::from_static("2"));
+        assert_eq!(
+            next_page(&current, &headers, Pagination::Offset)
+                .unwrap()
+                .unwrap()
+                .as_str(),
+            "https://gitlab.local/api/v4/projects/1/jobs?per_page=100&page=2"
+        );
+        assert_eq!(
+            next_page(&current, &headers, Pagination::Keyset).unwrap(),
+            None
+        );
+
+        // Links take precedence, always kept on the configured host
+        headers.insert(
+            "link",
+            HeaderValue::from_static(
+                "<http://other.host/api/v4/projects/1/jobs?id_after=42&per_page=100>; rel=\"next\", \
+                 <http://other.host/api/v4/projects/1/jobs?per_page=100>; rel=\"first\"",
+            ),
+        );
+        assert_eq!(
+            next_page(&current, &headers, Pagination::Keyset)
+                .unwrap()
+                .unwrap()
+                .as_str(),
+            "https://gitlab.local/api/v4/projects/1/jobs?id_after=42&per_page=100"
+        );
     }
 
     #[tokio::test]
     async fn test_get_job_info() {
-        init();
-
-        let config = Config::load_config().unwrap();
-
-        let api = GitlabJOB::new(&config);
-
-        let response = api
-            .get_jobs(ProjectID(config.project_id.unwrap()), JobScope::Canceled)
-            .await;
-
-        let job_test = response
-            .values()
-            .next()
-            .map(|jobs| jobs.iter().next().unwrap())
-            .unwrap();
-
-        let jobinfo = api
-            .get_info((
-                ProjectID(config.project_id.unwrap()),
-                JobID(job_test.id.unwrap()),
-            ))
-            .await;
-
-        debug!("Job informations: {:?}", jobinfo);
+        let (gitlab, mut api) = gitlab().await;
+
+        let jobinfo = api.get_info((ProjectID(20), JobID(201))).await.unwrap();
+        assert_eq!(
+            jobinfo,
+            JobInfo {
+                id: Some(201),
+                name: Some("deploy".to_owned()),
+                stage: Some("deploy".to_owned()),
+                environment: Some("production".to_owned()),
+                status: Some(JobScope::Manual),
+                url: Some(format!("{}/projects/20/-/jobs/201", gitlab.base_url())),
+                proj_name: Some("backend".to_owned()),
+                proj_id: Some(20),
+                pipeline_id: Some(2001),
+                source_id: None,
+                user_mail: Some("dev@test.tst".to_owned()),
+                branch: Some("v1.2.0".to_owned()),
+                git_tag: Some("v1.2.0".to_owned()),
+            }
+        );
+
+        // Trigger variables take precedence
+        let mut config = gitlab.config();
+        config.production_tag_key = Some("PROD_TAG".to_owned());
+        api = GitlabJOB::new(&config);
+        let jobinfo = api.get_info((ProjectID(20), JobID(200))).await.unwrap();
+        assert_eq!(jobinfo.git_tag.as_deref(), Some("v1.1.0"));
+        assert_eq!(jobinfo.user_mail.as_deref(), Some("user@test.tst"));
+
+        assert!(api.get_info((ProjectID(20), JobID(999))).await.is_err());
     }
 
     #[tokio::test]
-    #[ignore = "get infos form a specific job"]
-    async fn test_get_specif_job() {
-        init();
-
-        let config = Config::load_config().unwrap();
-
-        let api = GitlabJOB::new(&config);
-
-        // let specify_project = 518_u64;
-        // let specify_job = 20548_u64;
-        let specify_project = 45307263_u64;
-        let specify_job = 8169892267_u64;
-
-        let jobinfo = api
-            .get_info((ProjectID(specify_project), JobID(specify_job)))
-            .await;
-
-        debug!("Got JobInfo: {:?}", jobinfo);
+    async fn test_get_pipe_vars() {
+        let (gitlab, api) = gitlab().await;
+        let vars = (0..150)
+            .map(|num| (format!("KEY_{num}"), num.to_string()))
+            .collect::<Vec<_>>();
+        gitlab.add_pipeline_vars(
+            20,
+            3000,
+            &vars
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect::<Vec<_>>(),
+        );
+
+        let got = api.get_pipe_vars(20, 3000).await.unwrap();
+        assert_eq!(got.len(), 150);
+        assert_eq!(got["KEY_149"], "149");
+
+        assert!(api.get_pipe_vars(20, 4000).await.unwrap().is_empty());
     }
 
-    // #[tokio::test]
-    // #[ignore = "It'll cancel a specific job"]
-    // async fn test_cancel_job() {
-    //     init();
-
-    //     let config = Config::load_config().unwrap();
-
-    //     let api = GitlabJOB::new(&config);
-
-    //     let specify_project = 306_u64;
-    //     let specify_job = 20753_u64;
+    #[tokio::test]
+    async fn test_get_git_tags() {
+        let (_gitlab, api) = gitlab().await;
 
-    //     let jobinfo = api.get_jobinfo(specify_project, specify_job).await;
+        let tags = api.get_tags(ProjectID(20)).await.unwrap();
+        assert_eq!(tags, ["v1.1.0", "v1.2.0"]);
+        assert!(api.get_tags(ProjectID(10)).await.unwrap().is_empty());
+    }
 
-    //     if let Err(resp) = api.cancel_job(&jobinfo.unwrap()).await {
-    //         panic!("Error {}", resp)
-    //     }
+    #[tokio::test]
+    async fn test_get_git_tag() {
+        let (gitlab, api) = gitlab().await;
+        let created_at = chrono::DateTime::parse_from_rfc3339("2024-05-01T10:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        gitlab.add_tag_at(20, "release/2024.05", created_at);
+        // Beyond the first page
+        (0..150).for_each(|num| gitlab.add_tags(20, &[&format!("v0.0.{num}")]));
+
+        assert_eq!(
+            api.get_tag(ProjectID(20), "release/2024.05").await.unwrap(),
+            Some(GitTag {
+                name: "release/2024.05".to_owned(),
+                created_at: Some(created_at),
+            })
+        );
+        assert!(api
+            .get_tag(ProjectID(20), "v0.0.149")
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(api.get_tag(ProjectID(20), "v9.9.9").await.unwrap(), None);
+        assert_eq!(api.get_tags(ProjectID(20)).await.unwrap().len(), 153);
+    }
 
-    //     debug!("Job canceled: {}", specify_job);
-    // }
+    #[tokio::test]
+    async fn test_protected_tags() {
+        let (gitlab, api) = gitlab().await;
+        gitlab.protect_tag(20, "v*");
+        gitlab.protect_tag(20, "release-*-final");
+
+        let protected = api.get_protected_tags(ProjectID(20)).await.unwrap();
+        assert_eq!(protected, ["v*", "release-*-final"]);
+        assert!(api
+            .get_protected_tags(ProjectID(10))
+            .await
+            .unwrap()
+            .is_empty());
+    }
 
-    // #[tokio::test]
-    // #[ignore = "It'll run a specific job"]
-    // async fn test_play_job() {
-    //     init();
+    #[tokio::test]
+    async fn test_get_job_status() {
+        let (_gitlab, api) = gitlab().await;
 
-    //     let config = Config::load_config().unwrap();
+        let job_test = api.get_info((ProjectID(20), JobID(150))).await.unwrap();
+        assert_eq!(api.get_status(&job_test).await.unwrap(), JobScope::Success);
+    }
 
-    //     let api = GitlabJOB::new(&config);
+    #[tokio::test]
+    async fn test_play_and_cancel_job() {
+        let (gitlab, api) = gitlab().await;
+
+        let played = api.get_info((ProjectID(20), JobID(200))).await.unwrap();
+        let (_, status) = api.play_job(&played).await.unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(gitlab.job_status(20, 200), Some(JobScope::Success));
+        // Only manual jobs can be played
+        assert!(api.play_job(&played).await.is_err());
+
+        let canceled = api.get_info((ProjectID(20), JobID(201))).await.unwrap();
+        let (_, status) = api.cancel_job(&canceled).await.unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(gitlab.job_status(20, 201), Some(JobScope::Canceled));
+    }
 
-    //     let specify_project = 306_u64;
-    //     let specify_job = 20752_u64;
+    #[tokio::test]
+    async fn test_retries() {
+        let (gitlab, api) = gitlab().await;
+
+        // GETs are retried on busy answers
+        gitlab.fail(
+            Method::GET,
+            "/api/v4/projects/20/repository/tags",
+            StatusCode::TOO_MANY_REQUESTS,
+            1,
+        );
+        gitlab.fail(
+            Method::GET,
+            "/api/v4/projects/20/repository/tags",
+            StatusCode::BAD_GATEWAY,
+            1,
+        );
+        assert_eq!(api.get_tags(ProjectID(20)).await.unwrap().len(), 2);
+
+        // Up to the configured attempts
+        gitlab.fail(
+            Method::GET,
+            "/api/v4/projects/20/repository/tags",
+            StatusCode::SERVICE_UNAVAILABLE,
+            3,
+        );
+        assert!(matches!(
+            api.get_tags(ProjectID(20)).await,
+            Err(Error::Status { status, .. }) if status == StatusCode::SERVICE_UNAVAILABLE
+        ));
+
+        // POSTs reached Gitlab, so they aren't retried
+        let job = api.get_info((ProjectID(20), JobID(200))).await.unwrap();
+        gitlab.fail(
+            Method::POST,
+            "/api/v4/projects/20/jobs/200/play",
+            StatusCode::BAD_GATEWAY,
+            1,
+        );
+        assert!(api.play_job(&job).await.is_err());
+        assert_eq!(gitlab.job_status(20, 200), Some(JobScope::Manual));
+        assert_eq!(
+            gitlab
+                .requests()
+                .iter()
+                .filter(|request| request.as_str() == "POST /api/v4/projects/20/jobs/200/play")
+                .count(),
+            1
+        );
+    }
 
-    //     let jobinfo = api.get_jobinfo(specify_project, specify_job).await;
-    //     debug!("To run job: {:?}", jobinfo);
+    /// Keeps what it's told about requests.
+    #[derive(Default)]
+    struct Recorder(std::sync::Mutex<Vec<(String, String, Option<u16>)>>);
+
+    impl RequestObserver for Recorder {
+        fn observe(
+            &self,
+            method: &str,
+            endpoint: &str,
+            status: Option<u16>,
+            _elapsed: std::time::Duration,
+        ) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((method.to_owned(), endpoint.to_owned(), status));
+        }
+    }
 
-    //     if let Err(resp) = api.play_job(&jobinfo.unwrap()).await {
-    //         panic!("Error {}", resp)
-    //     }
+    #[test]
+    fn test_job_text() {
+        let job = JobInfo {
+            id: Some(201),
+            proj_name: Some("backend".to_owned()),
+            git_tag: Some("v1.2.0".to_owned()),
+            status: Some(JobScope::Success),
+            ..Default::default()
+        };
+
+        let text = job.to_text();
+        assert!(text.starts_with("BACKEND: success\n"));
+        assert!(text.contains("Git tag:            v1.2.0\n"));
+        assert!(text.contains("Branch:             unknown\n"));
+        assert!(text.contains("Job id:             201\n"));
+        assert!(!text.contains('<'));
+    }
 
-    //     debug!("Job played: {}", specify_job);
-    // }
+    #[test]
+    fn test_endpoint() {
+        assert_eq!(
+            crate::endpoint("/api/v4/projects/20/jobs/200/play"),
+            "/api/v4/projects/:id/jobs/:id/play"
+        );
+        assert_eq!(
+            crate::endpoint("/api/v4/projects/20/repository/tags/release%2Fv1"),
+            "/api/v4/projects/:id/repository/tags/:name"
+        );
+        assert_eq!(
+            crate::endpoint("/api/v4/projects/20/repository/tags"),
+            "/api/v4/projects/:id/repository/tags"
+        );
+    }
 
     #[tokio::test]
-    async fn test_jobs_by_proj() {
-        init();
+    async fn test_observer() {
+        let (gitlab, _) = gitlab().await;
+        let recorder = std::sync::Arc::new(Recorder::default());
+        let api = GitlabJOB::new(&gitlab.config()).with_observer(recorder.clone());
+
+        gitlab.fail(
+            Method::GET,
+            "/api/v4/projects/20/repository/tags",
+            StatusCode::BAD_GATEWAY,
+            1,
+        );
+        api.get_tags(ProjectID(20)).await.unwrap();
+        let job = api.get_info((ProjectID(20), JobID(200))).await.unwrap();
+        api.play_job(&job).await.unwrap();
+
+        let tags = (
+            "GET".to_owned(),
+            "/api/v4/projects/:id/repository/tags".to_owned(),
+        );
+        let observed = recorder.0.lock().unwrap().clone();
+        assert_eq!(
+            observed.first(),
+            Some(&(tags.0.clone(), tags.1.clone(), Some(502)))
+        );
+        assert_eq!(observed.get(1), Some(&(tags.0, tags.1, Some(200))));
+        assert!(observed.contains(&(
+            "POST".to_owned(),
+            "/api/v4/projects/:id/jobs/:id/play".to_owned(),
+            Some(200)
+        )));
+    }
+}
 
-        let config = Config::load_config().unwrap();
+#[cfg(test)]
+mod test_connection_pool {
+    use core::convert::Infallible;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
 
-        let api = GitlabJOB::new(&config);
-        // let projid = 45307263;
-        let projid = config.project_id.unwrap();
+    use hyper::service::service_fn;
+    use hyper::{Body, Response};
+    use tokio_rustls::rustls;
 
-        let output = api.get_jobs(ProjectID(projid), JobScope::Success).await;
-        let mut total_jobs = 0;
+    use crate::prelude::*;
 
-        output.iter().for_each(|(projid, jobinfo)| {
-            debug!("************************\nGot Project: {}", projid.0);
-            jobinfo.iter().for_each(|job| {
-                total_jobs += 1;
-                debug!("=================\n{:?}", job);
-            });
+    /// Local https stand-in for Gitlab counting TLS handshakes, answering `[]` to everything.
+    async fn tls_server() -> (SocketAddr, reqwest::Certificate, Arc<AtomicUsize>) {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+        let cert_der = cert.serialize_der().unwrap();
+        let tls_config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![rustls::Certificate(cert_der.clone())],
+                rustls::PrivateKey(cert.serialize_private_key_der()),
+            )
+            .unwrap();
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(tls_config));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handshakes = Arc::new(AtomicUsize::new(0));
+
+        let counter = Arc::clone(&handshakes);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                let counter = Arc::clone(&counter);
+                tokio::spawn(async move {
+                    if let Ok(tls_stream) = acceptor.accept(stream).await {
+                        counter.fetch_add(1, Ordering::SeqCst);
+                        let service = service_fn(|_| async {
+                            Ok::<_, Infallible>(Response::new(Body::from("[]")))
+                        });
+                        let _ = hyper::server::conn::Http::new()
+                            .serve_connection(tls_stream, service)
+                            .await;
+                    }
+                });
+            }
         });
 
-        debug!("Got projects: {:?}", output.keys().len());
-        debug!("Total jobs: {}", total_jobs);
+        (
+            addr,
+            reqwest::Certificate::from_der(&cert_der).unwrap(),
+            handshakes,
+        )
     }
 
-    #[tokio::test]
-    async fn test_get_git_tags() {
-        init();
-
-        let config = Config::load_config().unwrap();
-
-        let projid = config.project_id.unwrap();
-
-        let api = GitlabJOB::new(&config);
-
-        let value = api.get_tags(ProjectID(projid)).await;
-
-        debug!("Project tags: {:?}", value);
+    fn config(addr: SocketAddr) -> Config {
+        Config {
+            base_url: Some(format!("https://localhost:{}", addr.port())),
+            private_token: Some("XXXXXXXXXXXXX".to_owned()),
+            ..Default::default()
+        }
     }
 
     #[tokio::test]
-    async fn test_get_job_status() {
-        init();
-
-        let config = Config::load_config().unwrap();
+    async fn test_tls_handshakes() {
+        const CALLS: usize = 20;
+        let (addr, cert, handshakes) = tls_server().await;
+        let api = GitlabJOB::new(&config(addr));
+
+        // Before: a new client for each call, as api_caller used to build
+        for _ in 0..CALLS {
+            let client = api
+                .api_builder()
+                .unwrap()
+                .add_root_certificate(cert.clone())
+                .resolve("localhost", addr)
+                .build()
+                .unwrap();
+            let url = api.gen_url("/api/v4/projects").unwrap();
+            client.get(url).send().await.unwrap().text().await.unwrap();
+        }
+        let before = handshakes.swap(0, Ordering::SeqCst);
+
+        // After: the client shared by every call
+        let shared = api
+            .api_builder()
+            .unwrap()
+            .add_root_certificate(cert)
+            .resolve("localhost", addr)
+            .build()
+            .unwrap();
+        assert!(api.client.set(shared).is_ok());
+        for _ in 0..CALLS {
+            api.get_json("/api/v4/projects").await.unwrap();
+        }
+        let after = handshakes.load(Ordering::SeqCst);
+
+        debug!("TLS handshakes for {CALLS} calls: {before} before, {after} after");
+        assert_eq!(before, CALLS);
+        assert_eq!(after, 1);
+    }
 
+    #[tokio::test]
+    async fn test_no_keep_alive() {
+        let (addr, cert, handshakes) = tls_server().await;
+        let mut config = config(addr);
+        config.http = Some(configloader::HttpConfig {
+            keep_alive: Some(0),
+            ..Default::default()
+        });
         let api = GitlabJOB::new(&config);
 
-        let specify_project = 306_u64;
-        let specify_job = 21295_u64;
-
-        let job_test = api
-            .get_info((ProjectID(specify_project), JobID(specify_job)))
-            .await
-            .unwrap_or_default();
-
-        let job_status = api.get_status(&job_test).await;
+        let client = api
+            .api_builder()
+            .unwrap()
+            .add_root_certificate(cert)
+            .resolve("localhost", addr)
+            .build()
+            .unwrap();
+        assert!(api.client.set(client).is_ok());
+        for _ in 0..3 {
+            api.get_json("/api/v4/projects").await.unwrap();
+        }
 
-        debug!("Current job status from job {job_test}: {:?}", job_status);
+        assert_eq!(handshakes.load(Ordering::SeqCst), 3);
     }
 }
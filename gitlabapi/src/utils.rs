@@ -2,6 +2,9 @@ use reqwest::header::{HeaderMap, HeaderValue};
 
 use crate::prelude::*;
 
+/// `User-Agent` header when none is configured.
+const DEFAULT_USER_AGENT: &str = concat!("gitlabapi/", env!("CARGO_PKG_VERSION"));
+
 #[non_exhaustive]
 pub enum HttpMethod {
     Options,
@@ -16,29 +19,31 @@ pub enum HttpMethod {
 }
 
 impl GitlabJOB {
-    pub fn api_caller(&self, url: &str, method: HttpMethod) -> reqwest::RequestBuilder {
-        let uri = self.gen_url(url);
-
-        match self.api_builder().build() {
-            Ok(http_client) => match method {
-                HttpMethod::Get => http_client.request(reqwest::Method::GET, uri),
-                HttpMethod::Post => http_client.request(reqwest::Method::POST, uri),
-                _ => http_client.request(reqwest::Method::GET, uri),
-            },
-
-            Err(error) => {
-                error!("Couldn't construct the api caller: {}", error);
-                std::process::exit(11)
-            }
-        }
+    pub fn api_caller(
+        &self,
+        url: &str,
+        method: HttpMethod,
+    ) -> Result<reqwest::RequestBuilder, Error> {
+        let uri = self.gen_url(url)?;
+        let http_client = self.client()?;
+
+        Ok(match method {
+            HttpMethod::Get => http_client.request(reqwest::Method::GET, uri),
+            HttpMethod::Post => http_client.request(reqwest::Method::POST, uri),
+            _ => http_client.request(reqwest::Method::GET, uri),
+        })
     }
 
-    pub fn api_get(&self, url: &str) -> reqwest::RequestBuilder {
+    pub fn api_get(&self, url: &str) -> Result<reqwest::RequestBuilder, Error> {
         self.api_caller(url, HttpMethod::Get)
     }
 
-    pub fn gen_url(&self, path: &str) -> reqwest::Url {
-        let base = self.config.base_url.clone().unwrap();
+    pub fn gen_url(&self, path: &str) -> Result<reqwest::Url, Error> {
+        let base = self
+            .config
+            .base_url
+            .clone()
+            .ok_or(Error::Config("base_url"))?;
 
         let new_uri = if path.starts_with('/') || base.ends_with('/') {
             base + path
@@ -46,43 +51,124 @@ impl GitlabJOB {
             base + "/" + path
         };
 
-        match reqwest::Url::parse(&new_uri) {
-            Ok(url) => url,
-            Err(error) => {
-                error!("Error while parsing url \"{}\": {}", new_uri, error);
-                std::process::exit(12)
-            }
+        reqwest::Url::parse(&new_uri).map_err(|error| Error::InvalidUrl {
+            url: new_uri,
+            reason: error.to_string(),
+        })
+    }
+
+    /// The shared client, built on the first call.
+    pub fn client(&self) -> Result<&reqwest::Client, Error> {
+        if let Some(client) = self.client.get() {
+            return Ok(client);
         }
+
+        let client = self.api_builder()?.build()?;
+        Ok(self.client.get_or_init(|| client))
     }
 
-    pub fn api_builder(&self) -> reqwest::ClientBuilder {
+    pub fn api_builder(&self) -> Result<reqwest::ClientBuilder, Error> {
+        let token = self
+            .config
+            .private_token
+            .as_ref()
+            .ok_or(Error::Config("private_token"))?;
+
         let mut headers = HeaderMap::new();
         headers.insert(
             "PRIVATE-TOKEN",
-            HeaderValue::from_str(self.config.private_token.as_ref().unwrap()).unwrap(),
+            HeaderValue::from_str(token).map_err(|_| Error::Config("private_token"))?,
         );
-        headers.insert("Accept", HeaderValue::from_str("application/json").unwrap());
+        headers.insert("Accept", HeaderValue::from_static("application/json"));
         headers.insert(
             "Content-type",
-            HeaderValue::from_str("application/json; charset=utf-8").unwrap(),
+            HeaderValue::from_static("application/json; charset=utf-8"),
         );
 
-        reqwest::ClientBuilder::new().default_headers(headers)
+        let http = self.config.http.clone().unwrap_or_default();
+        let user_agent = http
+            .user_agent
+            .clone()
+            .unwrap_or_else(|| DEFAULT_USER_AGENT.to_owned());
+
+        let mut builder = reqwest::ClientBuilder::new()
+            .default_headers(headers)
+            .user_agent(user_agent)
+            .pool_idle_timeout(http.keep_alive());
+        if http.keep_alive().is_zero() {
+            builder = builder.pool_max_idle_per_host(0);
+        }
+        if let Some(timeout) = http.connect_timeout() {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = http.timeout() {
+            builder = builder.timeout(timeout);
+        }
+
+        Ok(builder)
     }
 
-    pub fn parse_json(text: String) -> Result<Value, String> {
-        if let Ok(parsed_json) = serde_json::from_str::<Value>(&text) {
-            Ok(parsed_json)
-        } else {
-            Err(format!("Error while parsing to json from: \n{}", text))
+    /// Wait a request step up to the read timeout, if any.
+    pub async fn read_timeout<T>(
+        &self,
+        step: impl core::future::Future<Output = Result<T, reqwest::Error>>,
+    ) -> Result<T, Error> {
+        match self
+            .config
+            .http
+            .as_ref()
+            .and_then(|http| http.read_timeout())
+        {
+            Some(limit) => tokio::time::timeout(limit, step)
+                .await
+                .map_err(|_| Error::Timeout(limit))?
+                .map_err(Error::from),
+            None => step.await.map_err(Error::from),
         }
     }
 
-    pub fn api_post(&self, url: &str, json: Value) -> reqwest::RequestBuilder {
+    pub fn parse_json(text: String) -> Result<Value, Error> {
+        serde_json::from_str::<Value>(&text).map_err(|source| Error::Json { source, body: text })
+    }
+
+    pub fn api_post(&self, url: &str, json: Value) -> Result<reqwest::RequestBuilder, Error> {
         // let post_json = serde_json::json!(form);
 
         debug!("Post JSON: {}", json);
 
-        self.api_caller(url, HttpMethod::Post).json(&json)
+        Ok(self.api_caller(url, HttpMethod::Post)?.json(&json))
+    }
+
+    /// Read the body of a response, failing on unsuccessful statuses.
+    pub async fn response_text(&self, response: reqwest::Response) -> Result<String, Error> {
+        let status = response.status();
+        let text = self.read_timeout(response.text()).await?;
+
+        if status.is_success() {
+            Ok(text)
+        } else {
+            Err(Error::from_status(status, text))
+        }
+    }
+
+    /// Percent-encode a value used as a single path segment, like a tag name holding `/`.
+    pub fn encode_segment(value: &str) -> String {
+        value
+            .bytes()
+            .map(|byte| match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                    char::from(byte).to_string()
+                }
+                _ => format!("%{byte:02X}"),
+            })
+            .collect()
+    }
+
+    /// Build the API path of a job.
+    pub fn job_path(job: &JobInfo) -> Result<String, Error> {
+        let proj_id = job.proj_id.ok_or(Error::IncompleteJob("project id"))?;
+        let job_id = job.id.ok_or(Error::IncompleteJob("id"))?;
+
+        Ok(format!("/api/v4/projects/{proj_id}/jobs/{job_id}"))
     }
 }
@@ -50,11 +50,8 @@ impl MailSender {
     fn try_build_relay(&mut self) -> Result<(), String> {
         let wait_time = Some(Duration::from_secs(20));
 
-        let creds = if self.user.is_some() && self.pass.is_some() {
-            Some(Credentials::new(
-                self.user.as_ref().unwrap().to_owned(),
-                self.pass.as_ref().unwrap().to_owned(),
-            ))
+        let creds = if let (Some(user), Some(pass)) = (&self.user, &self.pass) {
+            Some(Credentials::new(user.to_owned(), pass.to_owned()))
         } else {
             warn!("Proceeding with unauthenticated smtp connection");
             None
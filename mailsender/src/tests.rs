@@ -81,15 +81,51 @@ mod test_mail {
 This is a <b>test message</b>. :-)
 "#;
 
-        let mail_message =
-            config
-                .smtp
-                .unwrap()
-                .body_builder("Test subject".to_owned(), message.to_owned(), &None);
+        let mail_message = config.smtp.unwrap().body_builder(
+            "Test subject".to_owned(),
+            message.to_owned(),
+            "This is a test message. :-)".to_owned(),
+            None,
+        );
 
         debug!("{:?}", mail_message);
     }
 
+    #[test]
+    fn test_report_builder() {
+        let config = SmtpConfig {
+            from: Some("jobber@test.tst".to_owned()),
+            subject: Some("[Jobs] ".to_owned()),
+            ..Default::default()
+        };
+
+        let report = config
+            .report_builder(
+                "Run report".to_owned(),
+                "<h1>Report</h1>".to_owned(),
+                "Report".to_owned(),
+                "dev@test.tst",
+            )
+            .unwrap();
+        let headers = report.headers();
+        assert_eq!(headers.get_raw("To"), Some("dev@test.tst"));
+        assert_eq!(headers.get_raw("Subject"), Some("[Jobs] Run report"));
+
+        let formatted = String::from_utf8(report.formatted()).unwrap();
+        assert!(formatted.contains("multipart/alternative"));
+        assert!(formatted.contains("Content-Type: text/plain"));
+        assert!(formatted.contains("Content-Type: text/html"));
+
+        assert!(config
+            .report_builder(
+                "Run report".to_owned(),
+                String::new(),
+                String::new(),
+                "not an address"
+            )
+            .is_none());
+    }
+
     #[tokio::test(flavor = "current_thread")]
     #[ignore = "It'll really send an email message"]
     async fn test_send_mail() {
@@ -107,13 +143,15 @@ This is a <b>test message</b>. :-)
         let mail_message = config.smtp.clone().unwrap().body_builder(
             "Test subject".to_owned(),
             message.to_owned(),
-            &None,
+            "Test\n\nThis is a test message. :-)".to_owned(),
+            None,
         );
 
         let mail_message2 = config.smtp.unwrap().body_builder(
             "Test subject".to_owned(),
             "Another message test".to_owned(),
-            &None,
+            "Another message test".to_owned(),
+            None,
         );
 
         let mailsender = MailSender::try_new(smtp_config.unwrap()).await.unwrap();
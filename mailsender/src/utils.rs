@@ -24,13 +24,24 @@ pub trait SmtpUtils {
         Some((server, port))
     }
 
+    /// Message with an HTML body and its plain text alternative.
     // Yeah, I liked this pun
     fn body_builder(
         &self,
         subject: String,
         message: String,
+        text: String,
         destination: Option<String>,
     ) -> Message;
+
+    /// Message sent to the given recipients only, `None` when an address is invalid.
+    fn report_builder(
+        &self,
+        subject: String,
+        message: String,
+        text: String,
+        recipients: &str,
+    ) -> Option<Message>;
 }
 
 impl SmtpUtils for SmtpConfig {
@@ -38,6 +49,7 @@ impl SmtpUtils for SmtpConfig {
         &self,
         subject: String,
         message: String,
+        text: String,
         destination: Option<String>,
     ) -> Message {
         if !self.is_valid() {
@@ -78,8 +90,7 @@ impl SmtpUtils for SmtpConfig {
             // .to(self.to.as_ref().unwrap().parse().unwrap())
             // .to(to)
             .subject(concat_subject)
-            .header(ContentType::TEXT_HTML)
-            .body(message)
+            .multipart(MultiPart::alternative_plain_html(text, message))
         {
             Ok(message) => message,
             Err(_) => {
@@ -88,4 +99,26 @@ impl SmtpUtils for SmtpConfig {
             }
         }
     }
+
+    fn report_builder(
+        &self,
+        subject: String,
+        message: String,
+        text: String,
+        recipients: &str,
+    ) -> Option<Message> {
+        let to: Mailboxes = recipients.parse().ok()?;
+        let to_header: lettre::message::header::To = to.into();
+
+        MessageBuilder::new()
+            .mailbox(to_header)
+            .from(self.from.as_ref()?.parse().ok()?)
+            .subject(format!(
+                "{}{}",
+                self.subject.clone().unwrap_or_default(),
+                subject
+            ))
+            .multipart(MultiPart::alternative_plain_html(text, message))
+            .ok()
+    }
 }
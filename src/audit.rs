@@ -0,0 +1,154 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use configloader::AuditConfig;
+use gitlabapi::prelude::*;
+
+use crate::jsonl::JsonLines;
+use crate::Decision;
+
+/// What an audit line tells about a job.
+#[non_exhaustive]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent {
+    /// The job was found by a scan.
+    Scanned,
+    /// Outcome of the job validation.
+    Decided {
+        decision: Decision,
+        reason: Option<String>,
+        /// Details of the reason, like the broken tag rule or the failed prerequisite.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        details: Option<String>,
+    },
+    /// A play request is about to be sent.
+    PlayRequested,
+    /// Answer to the play request.
+    PlayAnswered(Answer),
+    /// A cancel request is about to be sent.
+    CancelRequested,
+    /// Answer to the cancel request.
+    CancelAnswered(Answer),
+    /// Final status seen while monitoring the job.
+    Finished { status: String },
+    /// The job wasn't over within the max waiting time.
+    TimedOut,
+}
+
+/// Outcome of a play or cancel request.
+#[non_exhaustive]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Answer {
+    pub succeeded: bool,
+    /// Status Gitlab answered with, unknown when the request couldn't be sent.
+    pub http_status: Option<u16>,
+    pub error: Option<String>,
+}
+
+impl Answer {
+    pub fn from_result(result: &Result<(&JobInfo, StatusCode), Error>) -> Self {
+        match result.as_ref() {
+            Ok(&(_, status)) => Self {
+                succeeded: true,
+                http_status: Some(status.as_u16()),
+                error: None,
+            },
+            Err(error) => Self {
+                succeeded: false,
+                http_status: error.status().map(|status| status.as_u16()),
+                error: Some(error.to_string()),
+            },
+        }
+    }
+}
+
+/// A line of the audit log.
+#[derive(Serialize, Debug)]
+struct AuditRecord<'job> {
+    timestamp: DateTime<Utc>,
+    job_id: Option<u64>,
+    pipeline_id: Option<u64>,
+    project_id: Option<u64>,
+    source_id: Option<u64>,
+    user_mail: Option<&'job str>,
+    git_tag: Option<&'job str>,
+    #[serde(flatten)]
+    event: AuditEvent,
+}
+
+/// Append-only file of JSON lines telling who triggered each job and what was done with it.
+pub struct AuditLog {
+    file: JsonLines,
+    max_size: Option<u64>,
+    keep: u32,
+}
+
+impl AuditLog {
+    /// Audit log of the configuration, if there's a path for it.
+    pub fn new(config: &AuditConfig) -> Option<Self> {
+        Some(Self {
+            file: JsonLines::new(PathBuf::from(config.path.as_ref()?)),
+            max_size: config.max_size,
+            keep: config.keep(),
+        })
+    }
+
+    /// Append an event of a job, logging failures.
+    pub fn write(&self, job: &JobInfo, event: AuditEvent) {
+        let record = AuditRecord {
+            timestamp: Utc::now(),
+            job_id: job.id,
+            pipeline_id: job.pipeline_id,
+            project_id: job.proj_id,
+            source_id: job.source_id,
+            user_mail: job.user_mail.as_deref(),
+            git_tag: job.git_tag.as_deref(),
+            event,
+        };
+        let written = self.file.append_with(&record, |length| {
+            let full = self.max_size.is_some_and(|max_size| {
+                let size = fs::metadata(self.file.path()).map_or(0, |metadata| metadata.len());
+                size > 0 && size.saturating_add(length) > max_size
+            });
+            if full {
+                self.rotate()
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(error) = written {
+            job_log!(
+                Error,
+                job,
+                "Couldn't write job {job} to audit log {}: {error}",
+                self.file.path().display()
+            );
+        }
+    }
+
+    /// Shift rotated files by one, dropping the oldest, and move the current file to `.1`.
+    fn rotate(&self) -> io::Result<()> {
+        if self.keep == 0 {
+            return fs::remove_file(self.file.path());
+        }
+
+        for number in (1..self.keep).rev() {
+            let older = self.rotated(number);
+            if older.exists() {
+                fs::rename(older, self.rotated(number.saturating_add(1)))?;
+            }
+        }
+        fs::rename(self.file.path(), self.rotated(1))
+    }
+
+    fn rotated(&self, number: u32) -> PathBuf {
+        let mut name = self.file.path().to_path_buf().into_os_string();
+        name.push(format!(".{number}"));
+        PathBuf::from(name)
+    }
+}
@@ -0,0 +1,102 @@
+use clap::{Args, Parser, Subcommand};
+
+use configloader::Config;
+
+/// Starts, cancels and watches Gitlab manual jobs.
+#[non_exhaustive]
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(flatten)]
+    pub overrides: Overrides,
+    /// What to do, `run` when omitted.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Options taking precedence over the loaded configurations.
+#[non_exhaustive]
+#[derive(Args, Debug, Default)]
+pub struct Overrides {
+    /// Gitlab group to scan for manual jobs, replacing the configured groups and projects.
+    #[arg(long, global = true)]
+    pub group_id: Option<u64>,
+    /// Gitlab project to scan for manual jobs, replacing the configured groups and projects.
+    #[arg(long, global = true)]
+    pub project_id: Option<u64>,
+    /// Max waiting time for a job in seconds.
+    #[arg(long, global = true)]
+    pub max_wait_time: Option<u64>,
+    /// Keep running, scanning for jobs every N seconds.
+    #[arg(long, global = true)]
+    pub scan_interval: Option<u64>,
+    /// Only print what would be done to the found jobs.
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+}
+
+impl Overrides {
+    /// Replace configuration fields by the given options.
+    pub fn apply(&self, config: &mut Config) {
+        // Given scan targets replace every configured one
+        if self.group_id.is_some() || self.project_id.is_some() {
+            config.group_id = self.group_id;
+            config.project_id = self.project_id;
+            config.group_ids = None;
+            config.project_ids = None;
+        }
+        config.max_wait_time = self.max_wait_time.or(config.max_wait_time);
+        config.scan_interval = self.scan_interval.or(config.scan_interval);
+        if self.dry_run {
+            config.dry_run = Some(true);
+        }
+    }
+}
+
+#[non_exhaustive]
+#[derive(Subcommand, Debug, Default, PartialEq, Eq)]
+pub enum Command {
+    /// Scan for manual jobs, playing or canceling them.
+    #[default]
+    Run,
+    /// List pending manual jobs.
+    List,
+    /// Play a manual job.
+    Play {
+        /// Project id.
+        project: u64,
+        /// Job id.
+        job: u64,
+    },
+    /// Cancel a job.
+    Cancel {
+        /// Project id.
+        project: u64,
+        /// Job id.
+        job: u64,
+    },
+    /// Show the current status of a job.
+    Status {
+        /// Project id.
+        project: u64,
+        /// Job id.
+        job: u64,
+    },
+    /// Show what happened to a job, from the state file.
+    History {
+        /// Job id.
+        job: u64,
+    },
+    /// Configuration helpers.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[non_exhaustive]
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum ConfigCommand {
+    /// Validate the loaded configurations.
+    Check,
+}
@@ -0,0 +1,222 @@
+use alloc::sync::Arc;
+use core::time::Duration;
+use std::io::Write as _;
+use std::path::Path;
+use std::process::ExitCode;
+
+use log::{error, info};
+
+use configloader::prelude::*;
+use gitlabapi::prelude::*;
+
+use crate::audit::{Answer, AuditEvent, AuditLog};
+use crate::executor::Executor;
+use crate::state::{Event, StateStore};
+use crate::templates::MailTemplates;
+use crate::{utils, Decision};
+
+/// Exit code when there's no group nor project to scan.
+const NO_PROJECT: u8 = 2;
+
+fn has_projects(config: &Config) -> bool {
+    let found = !config.scan_groups().is_empty() || !config.scan_projects().is_empty();
+    if !found {
+        error!("There's no project to scan for jobs.");
+    }
+    found
+}
+
+fn exit_code(result: std::io::Result<()>) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            error!("Couldn't write the output: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Scan for manual jobs, playing or canceling them.
+pub async fn run(config: &Config) -> ExitCode {
+    if !has_projects(config) {
+        return ExitCode::from(NO_PROJECT);
+    }
+
+    if config.dry_run.unwrap_or_default() {
+        info!("Dry run: no job will be played or canceled and no mail will be sent");
+        return exit_code(Executor::new(config, None).plan().await);
+    }
+
+    // Build mail relay, kept alive for every cycle
+    let mail_relay = match tokio::task::spawn(utils::mailrelay_build(
+        config.smtp.clone().unwrap_or_default(),
+    ))
+    .await
+    {
+        Ok(mailer) => {
+            debug!("Mail relay built");
+            mailer
+        }
+        Err(error) => {
+            error!("Error setting up mail relay: {error}");
+            None
+        }
+    };
+
+    let executor = Arc::new(Executor::new(config, mail_relay));
+
+    match config.scan_interval.filter(|&interval| interval > 0) {
+        Some(interval) => {
+            executor.run_daemon(Duration::from_secs(interval)).await;
+        }
+        None => executor.run_once().await,
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// List pending manual jobs.
+pub async fn list(config: &Config) -> ExitCode {
+    if !has_projects(config) {
+        return ExitCode::from(NO_PROJECT);
+    }
+
+    exit_code(Executor::new(config, None).list().await)
+}
+
+/// Play or cancel a single job, without any validation, recording it as handled.
+pub async fn act(config: &Config, project: u64, job: u64, decision: Decision) -> ExitCode {
+    let api = GitlabJOB::new(config);
+
+    let jobinfo = match api.get_info((ProjectID(project), JobID(job))).await {
+        Ok(jobinfo) => jobinfo,
+        Err(error) => {
+            error!("Couldn't get job {job} from project {project}: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let audit = config.audit.as_ref().and_then(AuditLog::new);
+    let (requested, result) = if decision == Decision::Play {
+        (AuditEvent::PlayRequested, api.play_job(&jobinfo))
+    } else {
+        (AuditEvent::CancelRequested, api.cancel_job(&jobinfo))
+    };
+    if let Some(audit) = audit.as_ref() {
+        audit.write(&jobinfo, requested);
+    }
+    let result = result.await;
+    if let Some(audit) = audit.as_ref() {
+        let answered = if decision == Decision::Play {
+            AuditEvent::PlayAnswered(Answer::from_result(&result))
+        } else {
+            AuditEvent::CancelAnswered(Answer::from_result(&result))
+        };
+        audit.write(&jobinfo, answered);
+    }
+    if let Some(path) = config.state_file.as_ref() {
+        match StateStore::open(Path::new(path)) {
+            Ok(state) => {
+                let error = result.as_ref().err().map(ToString::to_string);
+                let event = if decision == Decision::Play {
+                    Event::Played {
+                        error,
+                        deadline: None,
+                    }
+                } else {
+                    Event::Canceled { error }
+                };
+                state.record(&jobinfo, event);
+            }
+            Err(error) => error!("Couldn't open the state file {path}: {error}"),
+        }
+    }
+
+    match result {
+        Ok((acted, _)) if decision == Decision::Play => {
+            job_log!(Info, acted, "Job {acted} played");
+            ExitCode::SUCCESS
+        }
+        Ok((acted, _)) => {
+            job_log!(Info, acted, "Job {acted} canceled");
+            ExitCode::SUCCESS
+        }
+        Err(_) => ExitCode::FAILURE,
+    }
+}
+
+/// Show the current status of a job.
+pub async fn status(config: &Config, project: u64, job: u64) -> ExitCode {
+    let api = GitlabJOB::new(config);
+
+    match api.get_info((ProjectID(project), JobID(job))).await {
+        Ok(jobinfo) => exit_code(writeln!(
+            std::io::stdout().lock(),
+            "{}",
+            utils::job_details(&jobinfo)
+        )),
+        Err(error) => {
+            error!("Couldn't get job {job} from project {project}: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Show what happened to a job, from the state file.
+pub fn history(config: &Config, job: u64) -> ExitCode {
+    let Some(path) = config.state_file.as_ref() else {
+        error!("There's no state_file configured.");
+        return ExitCode::FAILURE;
+    };
+
+    match StateStore::history(Path::new(path), job) {
+        Ok(records) => {
+            if records.is_empty() {
+                error!("There's no record of job {job}.");
+                return ExitCode::FAILURE;
+            }
+            exit_code(writeln!(
+                std::io::stdout().lock(),
+                "{}",
+                utils::history_table(&records)
+            ))
+        }
+        Err(error) => {
+            error!("Couldn't read the state file {path}: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Problems of the configurations, or the error they couldn't be loaded with.
+pub fn config_problems(loaded: Result<&Config, &str>) -> Vec<String> {
+    let config = match loaded {
+        Ok(config) => config,
+        Err(error) => return vec![error.to_owned()],
+    };
+
+    let mut problems = config.check();
+    if let Some(smtp) = config.smtp.as_ref() {
+        problems.extend(MailTemplates::check(smtp));
+    }
+    problems
+}
+
+/// Report every problem found in the loaded configurations.
+pub fn check_config(loaded: Result<&Config, &str>) -> ExitCode {
+    let problems = config_problems(loaded);
+    let mut stdout = std::io::stdout().lock();
+
+    if problems.is_empty() {
+        return exit_code(writeln!(stdout, "Configuration is valid"));
+    }
+
+    for problem in &problems {
+        if let Err(error) = writeln!(stdout, "{problem}") {
+            error!("Couldn't write the output: {error}");
+            break;
+        }
+    }
+
+    ExitCode::FAILURE
+}
@@ -0,0 +1,893 @@
+use alloc::sync::Arc;
+use core::pin::pin;
+use std::collections::{HashMap, HashSet};
+use std::io::Write as _;
+use std::path::Path;
+use std::sync::Mutex;
+
+use chrono::Utc;
+use futures::future::{self, BoxFuture, Either, FutureExt as _, Shared};
+use futures::lock::Mutex as AsyncMutex;
+use futures::stream::{self, StreamExt as _};
+use log::{error, info};
+use tokio::task::JoinHandle;
+use tokio::time as tktime;
+
+use configloader::prelude::*;
+use gitlabapi::prelude::*;
+use mailsender::prelude::*;
+
+use crate::audit::{Answer, AuditEvent, AuditLog};
+use crate::logging::JobContext;
+use crate::metrics::Metrics;
+use crate::state::{Event, StateStore};
+use crate::templates::MailTemplates;
+use crate::utils;
+use crate::{Decision, MailReason};
+
+/// Which Gitlab status must be waited.
+const PENDING_STATUS: [JobScope; 4] = [
+    JobScope::Pending,
+    JobScope::Running,
+    JobScope::WaitingForResource,
+    JobScope::Manual,
+];
+
+/// Keeps the API caller and the mail relay alive across scan cycles.
+pub struct Executor {
+    api: GitlabJOB,
+    smtp_config: SmtpConfig,
+    templates: MailTemplates,
+    mail_relay: Option<SmtpTransport>,
+    /// Reports of the run waiting to be mailed together, in digest mode.
+    digest: Option<Mutex<Vec<(JobInfo, MailReason)>>>,
+    /// Ids of acted jobs whose status is still being monitored, or waiting their turn.
+    monitored: Mutex<HashSet<u64>>,
+    /// Ids of jobs left manual after a failure in their concurrency group, never played again.
+    skipped: Mutex<HashSet<u64>>,
+    /// Turns of the concurrency groups, held while one of their jobs runs.
+    turns: Mutex<HashMap<String, Arc<AsyncMutex<()>>>>,
+    /// Record of handled jobs, kept across runs.
+    state: Option<StateStore>,
+    /// Last decision recorded for each job, so a job deferred scan after scan is recorded once.
+    decided: Mutex<HashMap<u64, Event>>,
+    audit: Option<AuditLog>,
+    /// Counters of the run, also told about every Gitlab request.
+    metrics: Arc<Metrics>,
+}
+
+impl Executor {
+    pub fn new(config: &Config, mail_relay: Option<SmtpTransport>) -> Self {
+        let metrics = Arc::new(Metrics::default());
+        let observer: Arc<dyn RequestObserver> = Arc::<Metrics>::clone(&metrics);
+        let state =
+            config
+                .state_file
+                .as_ref()
+                .and_then(|path| match StateStore::open(Path::new(path)) {
+                    Ok(state) => Some(state),
+                    Err(error) => {
+                        error!("Couldn't open the state file {path}, running without it: {error}");
+                        None
+                    }
+                });
+        Self {
+            api: GitlabJOB::new(config).with_observer(observer),
+            smtp_config: config.smtp.clone().unwrap_or_default(),
+            templates: MailTemplates::new(&config.smtp.clone().unwrap_or_default()),
+            digest: config
+                .smtp
+                .as_ref()
+                .and_then(|smtp| smtp.digest)
+                .unwrap_or_default()
+                .then(|| Mutex::new(vec![])),
+            mail_relay,
+            monitored: Mutex::new(HashSet::new()),
+            skipped: Mutex::new(HashSet::new()),
+            turns: Mutex::new(HashMap::new()),
+            decided: Mutex::new(
+                state
+                    .as_ref()
+                    .map(|state| state.decisions().clone())
+                    .unwrap_or_default(),
+            ),
+            state,
+            audit: config.audit.as_ref().and_then(AuditLog::new),
+            metrics,
+        }
+    }
+
+    /// Run a single cycle and wait for every acted job to finish.
+    pub async fn run_once(self: &Arc<Self>) {
+        let mut monitors = self.resume();
+        monitors.extend(self.cycle().await);
+
+        debug!("Wait emails sendings.");
+        for monitor in monitors {
+            if let Err(error) = monitor.await {
+                error!("Job monitor stopped unexpectedly: {error}");
+            }
+        }
+        self.export_metrics();
+        self.send_digest();
+    }
+
+    /// Print what a cycle would do, without acting on jobs nor sending mails.
+    pub async fn plan(&self) -> std::io::Result<()> {
+        let proj_jobs = self.scan().await;
+        let verified_jobs = utils::validate_jobs(&self.api, &proj_jobs).await;
+
+        writeln!(
+            std::io::stdout().lock(),
+            "{}",
+            utils::plan_table(&verified_jobs)
+        )
+    }
+
+    /// Print pending manual jobs.
+    pub async fn list(&self) -> std::io::Result<()> {
+        let proj_jobs = self.scan().await;
+
+        writeln!(
+            std::io::stdout().lock(),
+            "{}",
+            utils::jobs_table(&proj_jobs)
+        )
+    }
+
+    /// Rescan for jobs every `interval` until a shutdown signal is received.
+    pub async fn run_daemon(self: &Arc<Self>, interval: tktime::Duration) {
+        let mut ticker = tktime::interval(interval);
+        ticker.set_missed_tick_behavior(tktime::MissedTickBehavior::Delay);
+
+        info!(
+            "Running as daemon, scanning every {} seconds",
+            interval.as_secs()
+        );
+
+        if let Some(addr) = self
+            .api
+            .config
+            .metrics
+            .as_ref()
+            .and_then(MetricsConfig::listen_addr)
+        {
+            drop(tokio::spawn(Arc::clone(&self.metrics).serve(addr)));
+        }
+
+        // Resumed monitors keep running detached as well
+        drop(self.resume());
+
+        // The signal is only checked between cycles, so a cycle started is never cut in the
+        // middle of its play and cancel requests
+        let mut shutdown = pin!(tokio::signal::ctrl_c());
+        loop {
+            if let Either::Left(_) = future::select(shutdown.as_mut(), pin!(ticker.tick())).await {
+                info!("Shutdown signal received, leaving daemon mode");
+                break;
+            }
+
+            // Monitors keep running detached while the next scans happen
+            drop(self.cycle().await);
+            self.export_metrics();
+            self.send_digest();
+        }
+
+        // Reports kept since the last cycle aren't lost
+        self.export_metrics();
+        self.send_digest();
+    }
+
+    /// Scan, validate and act on manual jobs, returning the spawned job monitors.
+    async fn cycle(self: &Arc<Self>) -> Vec<JoinHandle<()>> {
+        let proj_jobs = self.scan().await;
+
+        info!(
+            "Projects with {} status jobs: {:?}",
+            JobScope::Manual,
+            proj_jobs.keys()
+        );
+
+        self.metrics
+            .discovered(proj_jobs.iter().map(|(proj, jobs)| (proj.0, jobs.len())));
+        for jobs in proj_jobs.values() {
+            for job in jobs {
+                self.audit(job, AuditEvent::Scanned);
+            }
+        }
+
+        let verified_jobs = utils::validate_jobs(&self.api, &proj_jobs).await;
+        self.record_decisions(&verified_jobs);
+
+        // Jobs of the deploy graph wait for their prerequisites
+        let (mut in_turn, mut turns) = self.play_in_order(&verified_jobs);
+
+        // Jobs of a concurrency group are played one after another, older pipelines first
+        let mut queues: HashMap<String, Vec<JobInfo>> = HashMap::new();
+        for (&job, context) in &verified_jobs {
+            let group = job
+                .proj_id
+                .filter(|_| context.0 == Decision::Play && !in_turn.contains(job))
+                .and_then(|proj| self.api.config.concurrency_group(proj));
+            if let Some(group) = group {
+                if let Some(id) = job.id {
+                    self.set_monitored(id, true);
+                }
+                queues.entry(group).or_default().push(job.clone());
+                in_turn.insert(job);
+            }
+        }
+        turns.extend(queues.into_iter().map(|(group, mut jobs)| {
+            jobs.sort_by_key(|job| (job.pipeline_id, job.id));
+            let executor = Arc::clone(self);
+            tokio::spawn(async move { executor.play_in_turn(&group, jobs).await })
+        }));
+
+        let actions = stream::iter(&verified_jobs)
+            .filter(|&(job, context)| {
+                future::ready(context.0 != Decision::Defer && !in_turn.contains(job))
+            })
+            .map(|(&job, context)| async move {
+                let result = if context.0 == Decision::Play {
+                    self.play(job).await
+                } else {
+                    self.cancel(job).await
+                };
+                (job, result, context)
+            })
+            .buffer_unordered(STREAM_BUFF_SIZE)
+            .fuse()
+            .collect::<Vec<_>>()
+            .await;
+
+        if !actions.is_empty() {
+            info!("All jobs were triggered. Now I'll wait theirs endings...");
+        }
+
+        let monitors = actions
+            .into_iter()
+            .filter_map(|(job, result, context)| match result {
+                Ok(_) => {
+                    if let Some(id) = job.id {
+                        self.set_monitored(id, true);
+                    }
+                    let executor = Arc::clone(self);
+                    let job = job.clone();
+                    let reason = context.1.clone();
+                    Some(tokio::spawn(async move {
+                        executor.monitor(job, reason).await;
+                    }))
+                }
+                Err(error) => {
+                    let reason = if context.0 == Decision::Play {
+                        MailReason::ErrorToPlay
+                    } else {
+                        MailReason::ErrorToCancel
+                    };
+                    if self.mail_relay.is_some() {
+                        let mut job = job.clone();
+                        job.status = Some(JobScope::Invalid);
+                        self.notify(&job, &reason);
+                    } else {
+                        job_log!(
+                            Error,
+                            job,
+                            "Fail to act on job {job}: {}, {error}",
+                            reason.name()
+                        );
+                    }
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        turns.extend(monitors);
+        turns
+    }
+
+    /// Play the jobs taking part in the deploy graph, each one once its prerequisites succeeded,
+    /// returning them with their spawned tasks. Jobs needing themselves, or waiting for such a
+    /// job, are left out of the graph and played as the jobs without prerequisites.
+    fn play_in_order<'job_info>(
+        self: &Arc<Self>,
+        verified_jobs: &HashMap<&'job_info JobInfo, (Decision, Option<MailReason>)>,
+    ) -> (HashSet<&'job_info JobInfo>, Vec<JoinHandle<()>>) {
+        let to_play = verified_jobs
+            .iter()
+            .filter(|&(_, context)| context.0 == Decision::Play)
+            .map(|(&job, _)| job)
+            .collect::<Vec<_>>();
+        let needs = to_play
+            .iter()
+            .map(|&job| {
+                let prerequisites =
+                    utils::prerequisites(&self.api.config, job, to_play.iter().copied());
+                (job, prerequisites)
+            })
+            .filter(|entry| !entry.1.is_empty())
+            .collect::<HashMap<_, _>>();
+        let mut in_graph = needs
+            .iter()
+            .flat_map(|(&job, prerequisites)| prerequisites.iter().copied().chain([job]))
+            .collect::<HashSet<_>>();
+
+        // Outcomes are built from the jobs without prerequisites to their dependents
+        let mut outcomes: HashMap<&JobInfo, Shared<BoxFuture<'static, bool>>> = HashMap::new();
+        let mut pending = in_graph.iter().copied().collect::<Vec<_>>();
+        let mut tasks = vec![];
+        while !pending.is_empty() {
+            let (ready, waiting): (Vec<_>, Vec<_>) = pending.into_iter().partition(|job| {
+                needs.get(job).is_none_or(|prerequisites| {
+                    prerequisites
+                        .iter()
+                        .all(|prerequisite| outcomes.contains_key(prerequisite))
+                })
+            });
+            if ready.is_empty() {
+                for job in waiting {
+                    job_log!(
+                        Error,
+                        job,
+                        "The job {job} needs itself through its prerequisites, \
+                        playing it without waiting for them"
+                    );
+                    in_graph.remove(job);
+                }
+                break;
+            }
+
+            for job in ready {
+                let prerequisites = needs
+                    .get(job)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|&prerequisite| {
+                        let name = prerequisite.to_string();
+                        let outcome = outcomes.get(prerequisite)?.clone();
+                        Some(async move { (name, outcome.await) })
+                    })
+                    .collect::<Vec<_>>();
+
+                if let Some(id) = job.id {
+                    self.set_monitored(id, true);
+                }
+                let executor = Arc::clone(self);
+                let owned = job.clone();
+                let outcome = async move {
+                    let results = future::join_all(prerequisites).await;
+                    executor.play_after(owned, results).await
+                }
+                .boxed()
+                .shared();
+
+                outcomes.insert(job, outcome.clone());
+                tasks.push(tokio::spawn(async move {
+                    outcome.await;
+                }));
+            }
+            pending = waiting;
+        }
+
+        (in_graph, tasks)
+    }
+
+    /// Play a job once its prerequisites ended, in its concurrency group turn, or cancel it
+    /// when one of them failed. Returns whether the job succeeded.
+    async fn play_after(&self, mut job: JobInfo, prerequisites: Vec<(String, bool)>) -> bool {
+        if let Some((failed, _)) = prerequisites.into_iter().find(|&(_, succeeded)| !succeeded) {
+            job_log!(
+                Warn,
+                &job,
+                "The job {job} will be canceled, its prerequisite {failed} didn't succeed"
+            );
+            self.cancel_and_notify(&mut job, MailReason::PrerequisiteFailed(failed))
+                .await;
+            return false;
+        }
+
+        let turn = job
+            .proj_id
+            .and_then(|proj| self.api.config.concurrency_group(proj))
+            .map(|group| self.turn(&group));
+        let _turn = match turn.as_ref() {
+            Some(turn) => Some(turn.lock().await),
+            None => None,
+        };
+
+        match self.play(&job).await {
+            Ok(_) if turn.is_some() => {
+                self.monitor_in_turn(job, self.max_wait()).await == Some(JobScope::Success)
+            }
+            Ok(_) => self.monitor(job, None).await == Some(JobScope::Success),
+            Err(error) => {
+                job_log!(Error, &job, "Fail to play job {job}: {error}");
+                job.status = Some(JobScope::Invalid);
+                self.notify(&job, &MailReason::ErrorToPlay);
+                if let Some(id) = job.id {
+                    self.set_monitored(id, false);
+                }
+                false
+            }
+        }
+    }
+
+    /// Play the jobs of a concurrency group one at a time, each one after the previous one
+    /// succeeded. Once a job fails the remaining ones are left manual, for a person to decide
+    /// whether they still have to run.
+    async fn play_in_turn(&self, group: &str, jobs: Vec<JobInfo>) {
+        let turn = self.turn(group);
+        let _turn = turn.lock().await;
+
+        let mut failed: Option<u64> = None;
+        for mut job in jobs {
+            if let Some(previous) = failed {
+                job_log!(
+                    Warn,
+                    &job,
+                    "The job {job} is left manual, job {previous} of {group} failed"
+                );
+                self.skip(&job, previous);
+            } else {
+                match self.play(&job).await {
+                    Ok(_) => {
+                        job_log!(Debug, &job, "Job {job} of {group} played");
+                        if self.monitor_in_turn(job.clone(), self.max_wait()).await
+                            != Some(JobScope::Success)
+                        {
+                            failed = Some(job.id.unwrap_or_default());
+                        }
+                        continue;
+                    }
+                    Err(error) => {
+                        job_log!(Error, &job, "Fail to play job {job}: {error}");
+                        job.status = Some(JobScope::Invalid);
+                        self.notify(&job, &MailReason::ErrorToPlay);
+                        failed = Some(job.id.unwrap_or_default());
+                    }
+                }
+            }
+
+            if let Some(id) = job.id {
+                self.set_monitored(id, false);
+            }
+        }
+    }
+
+    /// Cancel a job and report it with the reason, or as an error to cancel keeping its status
+    /// when the request failed.
+    async fn cancel_and_notify(&self, job: &mut JobInfo, reason: MailReason) {
+        let cancel_error = self.cancel(job).await.err();
+        if let Some(error) = cancel_error {
+            job_log!(Error, &*job, "Fail to cancel job {job}: {error}");
+            self.notify(job, &MailReason::ErrorToCancel);
+        } else {
+            job.status = Some(JobScope::Canceled);
+            self.notify(job, &reason);
+        }
+        if let Some(id) = job.id {
+            self.set_monitored(id, false);
+        }
+    }
+
+    /// Leave a job of a concurrency group manual after a previous one failed, and report it.
+    fn skip(&self, job: &JobInfo, previous: u64) {
+        if let Some(id) = job.id {
+            if let Ok(mut skipped) = self.skipped.lock() {
+                skipped.insert(id);
+            }
+        }
+        self.record(job, Event::Skipped { after: previous });
+        self.notify(job, &MailReason::PreviousFailed(previous));
+    }
+
+    /// Lock held by the job of a concurrency group being played.
+    fn turn(&self, group: &str) -> Arc<AsyncMutex<()>> {
+        self.turns.lock().map_or_else(
+            |_| Arc::new(AsyncMutex::new(())),
+            |mut turns| Arc::clone(turns.entry(group.to_owned()).or_default()),
+        )
+    }
+
+    /// Look for manual jobs in every configured group and project at once, leaving out
+    /// excluded projects and the jobs already being monitored.
+    async fn scan(&self) -> HashMap<ProjectID, HashSet<JobInfo>> {
+        let config = &self.api.config;
+
+        let groups = stream::iter(config.scan_groups())
+            .map(|id| async move {
+                let jobs = self.api.get_jobs(GroupID(id), JobScope::Manual).await;
+                (format!("group {id}"), jobs)
+            })
+            .buffer_unordered(STREAM_BUFF_SIZE);
+        let projects = stream::iter(config.scan_projects())
+            .filter(|&id| future::ready(!config.is_excluded(id)))
+            .map(|id| async move {
+                let jobs = self.api.get_jobs(ProjectID(id), JobScope::Manual).await;
+                (format!("project {id}"), jobs)
+            })
+            .buffer_unordered(STREAM_BUFF_SIZE);
+        let scans = stream::select(groups, projects).collect::<Vec<_>>().await;
+
+        let mut proj_jobs: HashMap<ProjectID, HashSet<JobInfo>> = HashMap::new();
+        for (target, scanned) in scans {
+            match scanned {
+                Ok(found) => {
+                    for (proj, jobs) in found {
+                        proj_jobs.entry(proj).or_default().extend(jobs);
+                    }
+                }
+                Err(error) => error!("Couldn't scan {target} for manual jobs: {error}"),
+            }
+        }
+
+        // Jobs out of the filters are neither acted on nor reported
+        for (proj, jobs) in &mut proj_jobs {
+            if let Some(filter) = config.job_filter(proj.0) {
+                jobs.retain(|job| {
+                    filter.matches(
+                        job.name.as_deref(),
+                        job.stage.as_deref(),
+                        job.environment.as_deref(),
+                    )
+                });
+            }
+        }
+
+        if let Some(state) = self.state.as_ref() {
+            for jobs in proj_jobs.values_mut() {
+                jobs.retain(|job| job.id.is_none_or(|id| !state.is_handled(id)));
+            }
+        }
+        if let (Ok(monitored), Ok(skipped)) = (self.monitored.lock(), self.skipped.lock()) {
+            for jobs in proj_jobs.values_mut() {
+                jobs.retain(|job| {
+                    job.id
+                        .is_none_or(|id| !monitored.contains(&id) && !skipped.contains(&id))
+                });
+            }
+        }
+        proj_jobs.retain(|proj, jobs| !jobs.is_empty() && !config.is_excluded(proj.0));
+
+        proj_jobs
+    }
+
+    /// Wait for a job to reach a final status and report it, returning that status.
+    async fn monitor(&self, job: JobInfo, reason: Option<MailReason>) -> Option<JobScope> {
+        self.monitor_for(job, reason, self.max_wait()).await
+    }
+
+    /// Monitor a job holding its concurrency group turn. Past the max waiting time the job is
+    /// still waited for, without report, so the next job of the group never runs alongside it.
+    async fn monitor_in_turn(&self, job: JobInfo, max_wait: tktime::Duration) -> Option<JobScope> {
+        match self.monitor_for(job.clone(), None, max_wait).await {
+            Some(status) => Some(status),
+            None => self.wait_over(&job).await,
+        }
+    }
+
+    /// Wait for a job to reach a final status, returning it, or `None` when it can't be read.
+    async fn wait_over(&self, job: &JobInfo) -> Option<JobScope> {
+        let loop_wait_time = tktime::Duration::from_secs(10);
+        loop {
+            match self.api.get_status(job).await {
+                Ok(status) if !PENDING_STATUS.contains(&status) => return Some(status),
+                Ok(_) => job_log!(Debug, job, "Job {job} still holds its turn"),
+                Err(error) => {
+                    job_log!(
+                        Warn,
+                        job,
+                        "Couldn't get the status of job {job}, releasing its turn: {error}"
+                    );
+                    return None;
+                }
+            }
+            tktime::sleep(loop_wait_time).await;
+        }
+    }
+
+    /// Wait for a job to reach a final status for at most `max_wait`, and report it.
+    async fn monitor_for(
+        &self,
+        job: JobInfo,
+        reason: Option<MailReason>,
+        max_wait: tktime::Duration,
+    ) -> Option<JobScope> {
+        let cronometer = tktime::Instant::now();
+        let loop_wait_time = tktime::Duration::from_secs(10);
+
+        let mut status = None;
+        loop {
+            match self.api.get_status(&job).await {
+                Ok(curr_status) if !PENDING_STATUS.contains(&curr_status) => {
+                    let msg_reason = reason
+                        .as_ref()
+                        .filter(|_| curr_status == JobScope::Canceled)
+                        .cloned()
+                        .unwrap_or(MailReason::Status(curr_status));
+
+                    let mut job = job.clone();
+                    job.status = Some(curr_status);
+                    self.notify(&job, &msg_reason);
+
+                    job_log!(Info, &job, "Job {job} finished with status: {curr_status}");
+                    self.metrics.finished(curr_status, cronometer.elapsed());
+                    self.audit(
+                        &job,
+                        AuditEvent::Finished {
+                            status: curr_status.to_string(),
+                        },
+                    );
+                    self.record(
+                        &job,
+                        Event::Finished {
+                            status: curr_status.to_string(),
+                        },
+                    );
+                    status = Some(curr_status);
+                    break;
+                }
+                Ok(_) => job_log!(Debug, &job, "Waiting for job {job}"),
+                Err(error) => job_log!(Warn, &job, "Couldn't get the status of job {job}: {error}"),
+            }
+
+            if cronometer.elapsed() >= max_wait {
+                self.metrics.timed_out(cronometer.elapsed());
+                self.audit(&job, AuditEvent::TimedOut);
+                self.record(&job, Event::TimedOut);
+                self.notify(&job, &MailReason::MaxWaitElapsed);
+                job_log!(Warn, &job, "Job {job} elapsed max waiting time");
+                break;
+            }
+            tktime::sleep(loop_wait_time).await;
+        }
+
+        if let Some(id) = job.id {
+            self.set_monitored(id, false);
+        }
+        status
+    }
+
+    /// Play a job, recording the answer and until when it'll be monitored.
+    async fn play<'job_info>(&self, job: &'job_info JobInfo) -> Result<&'job_info JobInfo, Error> {
+        self.audit(job, AuditEvent::PlayRequested);
+        let result = self.api.play_job(job).await;
+        self.audit(job, AuditEvent::PlayAnswered(Answer::from_result(&result)));
+        if result.is_err() {
+            self.metrics.action_failed(Decision::Play);
+        }
+        let deadline = chrono::Duration::from_std(self.max_wait())
+            .ok()
+            .and_then(|max_wait| Utc::now().checked_add_signed(max_wait));
+        self.record(
+            job,
+            Event::Played {
+                error: result.as_ref().err().map(ToString::to_string),
+                deadline: deadline.filter(|_| result.is_ok()),
+            },
+        );
+        result.map(|(acted, _)| acted)
+    }
+
+    /// Longest wait for a job to finish.
+    fn max_wait(&self) -> tktime::Duration {
+        tktime::Duration::from_secs(self.api.config.max_wait_time.unwrap_or(30))
+    }
+
+    /// Watch again the jobs played by a previous run that didn't finish, until their deadline,
+    /// each one holding the turn of its concurrency group until it's over.
+    fn resume(self: &Arc<Self>) -> Vec<JoinHandle<()>> {
+        let Some(state) = self.state.as_ref() else {
+            return vec![];
+        };
+        let played = match state.in_flight() {
+            Ok(played) => played,
+            Err(error) => {
+                error!("Couldn't read the jobs played by a previous run: {error}");
+                return vec![];
+            }
+        };
+
+        played
+            .into_iter()
+            .filter_map(|(record, deadline)| {
+                let recorded = record.job();
+                let Some(project) = record.project_id else {
+                    job_log!(
+                        Warn,
+                        &recorded,
+                        "Job {recorded} was played without project, it can't be watched"
+                    );
+                    return None;
+                };
+                let max_wait = deadline
+                    .signed_duration_since(Utc::now())
+                    .to_std()
+                    .unwrap_or_default();
+                job_log!(
+                    Info,
+                    &recorded,
+                    "Resuming to watch job {recorded}, played at {}",
+                    record.at.to_rfc3339()
+                );
+
+                // Turns are taken before the first scan plays any job of the group
+                let turn = self
+                    .api
+                    .config
+                    .concurrency_group(project)
+                    .map(|group| self.turn(&group));
+                let taken = turn.as_ref().and_then(AsyncMutex::try_lock_owned);
+
+                self.set_monitored(record.job_id, true);
+                let executor = Arc::clone(self);
+                Some(tokio::spawn(async move {
+                    let held = match (taken, turn) {
+                        (Some(taken), _) => Some(taken),
+                        (None, Some(turn)) => Some(turn.lock_owned().await),
+                        (None, None) => None,
+                    };
+                    let job = executor
+                        .api
+                        .get_info((ProjectID(project), JobID(record.job_id)))
+                        .await;
+                    match (job, held) {
+                        (Ok(job), Some(held)) => {
+                            executor.monitor_in_turn(job, max_wait).await;
+                            drop(held);
+                        }
+                        (Ok(job), None) => {
+                            executor.monitor_for(job, None, max_wait).await;
+                        }
+                        (Err(error), _) => {
+                            job_log!(
+                                Error,
+                                &recorded,
+                                "Couldn't get job {recorded} to watch it: {error}"
+                            );
+                            executor.set_monitored(record.job_id, false);
+                        }
+                    }
+                }))
+            })
+            .collect()
+    }
+
+    /// Cancel a job, recording the answer.
+    async fn cancel<'job_info>(
+        &self,
+        job: &'job_info JobInfo,
+    ) -> Result<&'job_info JobInfo, Error> {
+        self.audit(job, AuditEvent::CancelRequested);
+        let result = self.api.cancel_job(job).await;
+        self.audit(
+            job,
+            AuditEvent::CancelAnswered(Answer::from_result(&result)),
+        );
+        if result.is_err() {
+            self.metrics.action_failed(Decision::Cancel);
+        }
+        self.record(
+            job,
+            Event::Canceled {
+                error: result.as_ref().err().map(ToString::to_string),
+            },
+        );
+        result.map(|(acted, _)| acted)
+    }
+
+    /// Write the validation decisions to the audit log and the state file, leaving out the ones
+    /// already recorded for the same job.
+    fn record_decisions(&self, verified_jobs: &HashMap<&JobInfo, (Decision, Option<MailReason>)>) {
+        for (job, context) in verified_jobs {
+            self.metrics.decided(context.0, context.1.as_ref());
+            job_log!(
+                Info,
+                JobContext::from(*job).decision(context.0),
+                "Job {job} decided: {}",
+                context.0
+            );
+            let reason = context.1.as_ref().map(MailReason::name).map(str::to_owned);
+            let event = Event::Decided {
+                decision: context.0,
+                reason: reason.clone(),
+            };
+            // A decision repeated scan after scan, like a deferred job, is only recorded once
+            let repeated = job.id.is_some_and(|id| {
+                self.decided.lock().is_ok_and(|mut decided| {
+                    decided.insert(id, event.clone()).as_ref() == Some(&event)
+                })
+            });
+            if repeated {
+                continue;
+            }
+
+            self.audit(
+                job,
+                AuditEvent::Decided {
+                    decision: context.0,
+                    reason,
+                    details: context.1.as_ref().and_then(MailReason::details),
+                },
+            );
+            self.record(job, event);
+        }
+    }
+
+    /// Rewrite the metrics textfile, if there's one.
+    fn export_metrics(&self) {
+        let textfile = self
+            .api
+            .config
+            .metrics
+            .as_ref()
+            .and_then(|metrics| metrics.textfile.as_ref());
+        if let Some(path) = textfile {
+            if let Err(error) = self.metrics.write_textfile(Path::new(path)) {
+                error!("Couldn't write the metrics to {path}: {error}");
+            }
+        }
+    }
+
+    /// Write an event of a job to the audit log, if there's one.
+    fn audit(&self, job: &JobInfo, event: AuditEvent) {
+        if let Some(audit) = self.audit.as_ref() {
+            audit.write(job, event);
+        }
+    }
+
+    /// Append an event of a job to the state file, if there's one.
+    fn record(&self, job: &JobInfo, event: Event) {
+        if let Some(state) = self.state.as_ref() {
+            state.record(job, event);
+        }
+    }
+
+    /// Send a mail report about a job, if there's a mail relay, or keep it for the digest.
+    fn notify(&self, job: &JobInfo, reason: &MailReason) {
+        if let (Some(_), Some(digest)) = (self.mail_relay.as_ref(), self.digest.as_ref()) {
+            if let Ok(mut entries) = digest.lock() {
+                entries.push((job.clone(), reason.clone()));
+            }
+            return;
+        }
+        if let Some(mailer) = self.mail_relay.as_ref() {
+            let message = utils::mail_message(job, reason, &self.smtp_config, &self.templates);
+            match mailer.send(&message) {
+                Ok(res) => job_log!(Debug, job, "Sent mail for job {job}: {}", res.code()),
+                Err(error) => job_log!(
+                    Error,
+                    job,
+                    "Fail to send a email for job {job}: {error}\n{message:?}"
+                ),
+            }
+        }
+    }
+
+    /// Mail the reports kept since the last digest, one per recipient.
+    fn send_digest(&self) {
+        let (Some(mailer), Some(digest)) = (self.mail_relay.as_ref(), self.digest.as_ref()) else {
+            return;
+        };
+        let entries = digest
+            .lock()
+            .map(|mut entries| core::mem::take(&mut *entries))
+            .unwrap_or_default();
+
+        for message in utils::digest_messages(&entries, &self.smtp_config) {
+            match mailer.send(&message) {
+                Ok(res) => debug!("Sent run report: {}", res.code()),
+                Err(error) => error!("Fail to send the run report: {error}"),
+            }
+        }
+    }
+
+    fn set_monitored(&self, id: u64, monitored: bool) {
+        if let Ok(mut jobs) = self.monitored.lock() {
+            if monitored {
+                jobs.insert(id);
+            } else {
+                jobs.remove(&id);
+            }
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+/// Append-only file of JSON lines, shared by the state file and the audit log.
+pub struct JsonLines {
+    path: PathBuf,
+    /// Serializes writes, so lines are never mixed.
+    writer: Mutex<()>,
+}
+
+impl JsonLines {
+    pub const fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            writer: Mutex::new(()),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append a value as a single line.
+    pub fn append<T: Serialize>(&self, value: &T) -> io::Result<()> {
+        self.append_with(value, |_| Ok(()))
+    }
+
+    /// Append a value as a single line, calling `prepare` with the line length first, while
+    /// no other line can be written, so the file can be rotated.
+    pub fn append_with<T, F>(&self, value: &T, prepare: F) -> io::Result<()>
+    where
+        T: Serialize,
+        F: FnOnce(u64) -> io::Result<()>,
+    {
+        let mut line = serde_json::to_string(value)?;
+        line.push('\n');
+
+        let _writing = self.writer.lock();
+        prepare(u64::try_from(line.len()).unwrap_or(u64::MAX))?;
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())
+    }
+}
@@ -0,0 +1,173 @@
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::io::Write as _;
+
+use chrono::{SecondsFormat, Utc};
+use log::kv::{self, Key, Value, VisitSource};
+use log::{Level, Log, Metadata, Record};
+use serde_json::{Map, Value as JsonValue};
+
+use configloader::LogFormat;
+use gitlabapi::prelude::JobInfo;
+
+use crate::Decision;
+
+/// Whether lines are written as JSON objects, set once the configurations are read.
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Log a message about a job, its fields going along in the JSON format.
+///
+/// Takes a level name, a `&JobInfo` or a `JobContext`, and the message format arguments.
+macro_rules! job_log {
+    ($level:ident, $context:expr, $($arg:tt)+) => {
+        $crate::logging::log_job(
+            log::Level::$level,
+            module_path!(),
+            &$crate::logging::JobContext::from($context),
+            format_args!($($arg)+),
+        )
+    };
+}
+
+/// Job fields attached to a log line.
+pub struct JobContext<'job> {
+    job: &'job JobInfo,
+    decision: Option<Decision>,
+}
+
+impl JobContext<'_> {
+    /// Add the decision taken for the job.
+    pub const fn decision(mut self, decision: Decision) -> Self {
+        self.decision = Some(decision);
+        self
+    }
+
+    /// Fields of the job known so far.
+    pub fn fields(&self) -> Vec<(&'static str, Value<'_>)> {
+        let numbers = [
+            ("job_id", self.job.id),
+            ("project_id", self.job.proj_id),
+            ("pipeline_id", self.job.pipeline_id),
+        ];
+        let mut fields = numbers
+            .into_iter()
+            .filter_map(|(key, value)| Some((key, Value::from(value?))))
+            .collect::<Vec<_>>();
+        if let Some(tag) = self.job.git_tag.as_deref() {
+            fields.push(("git_tag", Value::from(tag)));
+        }
+        if let Some(decision) = self.decision.as_ref() {
+            fields.push(("decision", Value::from_display(decision)));
+        }
+        if let Some(status) = self.job.status.as_ref() {
+            fields.push(("status", Value::from_display(status)));
+        }
+        fields
+    }
+}
+
+impl<'job> From<&'job JobInfo> for JobContext<'job> {
+    fn from(job: &'job JobInfo) -> Self {
+        Self {
+            job,
+            decision: None,
+        }
+    }
+}
+
+/// Send a line about a job to the logger, used through `job_log!`.
+pub fn log_job(level: Level, target: &str, context: &JobContext<'_>, message: fmt::Arguments<'_>) {
+    if level > log::max_level() {
+        return;
+    }
+    log::logger().log(
+        &Record::builder()
+            .level(level)
+            .target(target)
+            .args(message)
+            .key_values(&context.fields())
+            .build(),
+    );
+}
+
+/// Logs through one of two `env_logger` loggers, both filtered by `RUST_LOG`.
+struct Logger {
+    human: env_logger::Logger,
+    json: env_logger::Logger,
+}
+
+impl Logger {
+    fn current(&self) -> &env_logger::Logger {
+        if JSON.load(Ordering::Relaxed) {
+            &self.json
+        } else {
+            &self.human
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.current().enabled(metadata)
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        self.current().log(record);
+    }
+
+    fn flush(&self) {
+        self.current().flush();
+    }
+}
+
+/// Install the logger, in the human format until `set_format` is called.
+///
+/// The level is INFO by default, changed with the `RUST_LOG` environment variable.
+pub fn init() {
+    let env = || env_logger::Env::default().default_filter_or("info");
+    let human = env_logger::Builder::from_env(env()).build();
+    let json = env_logger::Builder::from_env(env())
+        .format(|buf, record| writeln!(buf, "{}", json_line(record)))
+        .build();
+
+    let max_level = human.filter();
+    if log::set_boxed_logger(Box::new(Logger { human, json })).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+/// A log line as a JSON object, with the fields of the job it's about.
+pub fn json_line(record: &Record<'_>) -> String {
+    let mut line = Map::new();
+    line.insert(
+        "timestamp".to_owned(),
+        Utc::now()
+            .to_rfc3339_opts(SecondsFormat::Millis, true)
+            .into(),
+    );
+    line.insert("level".to_owned(), record.level().as_str().into());
+    line.insert("target".to_owned(), record.target().into());
+    line.insert("message".to_owned(), record.args().to_string().into());
+    if let Err(error) = record.key_values().visit(&mut Fields(&mut line)) {
+        line.insert("fields_error".to_owned(), error.to_string().into());
+    }
+    JsonValue::Object(line).to_string()
+}
+
+/// Switch to the configured format.
+pub fn set_format(format: LogFormat) {
+    JSON.store(format == LogFormat::Json, Ordering::Relaxed);
+}
+
+/// Copies the fields of a log line to a JSON object, numbers staying numbers.
+struct Fields<'line>(&'line mut Map<String, JsonValue>);
+
+impl<'kvs> VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let value = value
+            .to_u64()
+            .map_or_else(|| value.to_string().into(), JsonValue::from);
+        self.0.insert(key.as_str().to_owned(), value);
+        Ok(())
+    }
+}
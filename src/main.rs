@@ -14,8 +14,93 @@
 //! base_url="https://gitlab.com/"
 //! project_id=123
 //! group_id=1
+//! group_ids=[2, 3] # More groups to scan
+//! project_ids=[456] # More projects to scan
+//! exclude_projects=[789] # Never scanned, even inside a scanned group
 //! production_tag_key="PROD_TAG" # Variable to search in a pipeline
 //! max_wait_time=1800 # Max waiting time for a job in seconds
+//! scan_interval=300 # Keep running, scanning for jobs every N seconds
+//! dry_run=false # Only print what would be done to the found jobs
+//! state_file="/var/lib/gitlabjobber/state.jsonl" # Record of handled jobs, kept across runs
+//! log_format="json" # human (default) or json, with job_id, project_id, pipeline_id... fields
+//!
+//! # Only play jobs inside these windows, deferring the others
+//! [[deploy_windows]]
+//! days=["Tue", "Wed", "Thu"]
+//! start="10:00"
+//! end="16:00"
+//! timezone="America/Sao_Paulo"
+//!
+//! # How group_id is scanned for projects
+//! [group]
+//! include_subgroups=true # Also scan projects of subgroups
+//! max_depth=2 # Levels of subgroups to scan, all of them when unset
+//! include_shared=false # Also scan projects shared with the group, true by default
+//!
+//! # Only manage these manual jobs, leaving the others untouched.
+//! # Patterns are globs, or regular expressions between slashes.
+//! [jobs]
+//! name.exclude=["cleanup*", "/^teardown-/"]
+//! stage.include=["deploy"]
+//! environment.include=["production", "staging"]
+//!
+//! # Rules deploy tags must follow, or their jobs are canceled
+//! [tags]
+//! pattern="v*" # Glob or /regular expression/
+//! semver=">=1.0, <3" # Version requirement, ignoring a leading v
+//! reject_prerelease=true # Refuse versions like 1.4.0-rc.1
+//! max_age=604800 # Oldest tag accepted in seconds
+//! require_protected=true # Only tags protected in the source project, jobs without tag canceled
+//!
+//! # Which manual jobs are duplicates, all but one being canceled
+//! [dedup]
+//! key="environment" # project (default), job_name, environment, branch or source_tag
+//! # Jobs missing the keyed field, like an environment, are never duplicates
+//! keep="semver" # pipeline (highest pipeline id, default) or semver (newest version tag)
+//!
+//! # Jobs played one at a time, older pipelines first, the next one once the previous succeeded.
+//! # Jobs queued after a failed one are left manual, and mailed about, for a person to decide.
+//! [concurrency]
+//! per_project=true # A single job at a time in each project
+//! [concurrency.groups] # Projects sharing a target, a single job at a time among them
+//! shared_db=[123, 456]
+//!
+//! # Deploy order: each key is played once its prerequisites in the same scan succeeded,
+//! # and canceled when one of them fails. Numbers are project ids, other names are job names.
+//! [needs]
+//! "456"=["123"]
+//! "deploy-frontend"=["deploy-backend", "789"] # Jobs needing themselves are played unordered
+//!
+//! # JSON lines telling who triggered each job, and what was decided and done with it
+//! [audit]
+//! path="/var/log/gitlabjobber/audit.jsonl"
+//! max_size=10485760 # Bytes the file is rotated at, never rotated when unset
+//! keep=5 # Rotated files kept as audit.jsonl.1, audit.jsonl.2...
+//!
+//! # Prometheus metrics of scans, decisions, job outcomes and Gitlab calls
+//! [metrics]
+//! listen="0.0.0.0:9184" # Serves /metrics while running as daemon
+//! textfile="/var/lib/node_exporter/gitlabjobber.prom" # For the node exporter textfile collector
+//!
+//! # Retries of Gitlab calls, GETs on errors and busy answers, play/cancel on connection errors
+//! [retry]
+//! attempts=3 # Tries of a call, the first one included
+//! base_delay=500 # Milliseconds before the first retry, doubled on each new try
+//! max_delay=60000 # Longest wait in milliseconds, also bounding Retry-After and RateLimit-Reset
+//!
+//! # Client of the Gitlab API, its connections are reused by every call
+//! [http]
+//! connect_timeout=10 # Seconds to establish a connection
+//! read_timeout=30 # Seconds waiting for Gitlab to answer
+//! timeout=60 # Seconds for a whole call
+//! user_agent="gitlabjobber"
+//! keep_alive=90 # Seconds an idle connection is kept, 0 to close it after each call
+//!
+//! # Settings for a specific project id, overriding the global ones
+//! [projects.123]
+//! deploy_windows=[] # No restriction for this project
+//! jobs.name.include=["deploy-*"] # Replaces the global [jobs] filter
+//! tags.reject_prerelease=false # Replaces the global [tags] rules
 //!
 //! [smtp]
 //! server="mail.com"
@@ -24,252 +109,220 @@
 //! to="destination@mail.com"
 //! subject="[Subject Prefix] "
 //! pass="Secret"
+//! digest=true # A single report per recipient after each run, instead of a mail per job
+//! [smtp.templates.duplicated] # Mail reason, like status, tag_rule or max_wait_elapsed
+//! subject="templates/duplicated.subject"
+//! html="templates/duplicated.html"
+//! text="templates/duplicated.txt"
 //! ```
 //!
 //! It also supports definition from environment variables, whom **takes precedence**.
 //!
 //! The SMTP section is only needed if you want to receive report emails.
 //! SMTP settings from environment variables must has `SMTP_` prefix.
+//! With `digest=true` the configured recipients get a report of every job of the run, sorted in
+//! played, failed, canceled and timed out jobs, and each user gets one about their own jobs.
+//! A daemon sends it after each scan, with what happened since the previous one.
+//!
+//! Subjects and bodies of the mails sent per job can come from
+//! [MiniJinja](https://docs.rs/minijinja) templates, set by mail reason in `[smtp.templates]`.
+//! They see every job field, like `id`, `name`, `proj_name`, `git_tag` or `status`, unknown ones
+//! being undefined, with `reason`, its `details` and the built-in subject as `summary`.
+//! Missing or broken templates fall back to the built-in ones, and `gitlabjobber config check`
+//! reports them.
+//!
+//! Without `scan_interval` it scans once, waits the started jobs and exits.
+//! With it, it runs as a daemon rescanning on that interval until interrupted.
+//!
+//! With `dry_run=true` it reads jobs, pipelines and tags from Gitlab as usual, but only
+//! prints a table with the decision for every found job, acting on none of them.
+//!
+//! With `state_file` every decision, play and cancel answer and final status is appended to
+//! that file, `play` and `cancel` commands included. Later runs skip the jobs it shows as already
+//! handled, and `gitlabjobber history` prints what happened to a job.
+//! A decision repeated by later scans, like a job deferred again, is only written once.
+//! Jobs played by a run that was stopped before they were over are watched again on startup,
+//! until their original deadline, before the first scan.
+//!
+//! Metrics count the jobs found in each project, decisions by reason, failed play and cancel
+//! requests and final job statuses, with histograms of the time spent watching jobs and of
+//! Gitlab API latency by endpoint and status. A one-shot run writes them to the `textfile` once
+//! its jobs are over, a daemon rewrites it after each scan.
+//!
+//! ## Command line
+//! Without arguments it runs as described above, the same as `gitlabjobber run`.
+//! Other subcommands help to handle jobs by hand:
+//!
+//! ```text
+//! gitlabjobber list                    # Pending manual jobs
+//! gitlabjobber play <project> <job>    # Play a manual job
+//! gitlabjobber cancel <project> <job>  # Cancel a job
+//! gitlabjobber status <project> <job>  # Current job status and informations
+//! gitlabjobber history <job>          # What happened to a job, from the state file
+//! gitlabjobber config check            # Validate configurations
+//! ```
+//!
+//! Options `--group-id`, `--project-id`, `--max-wait-time`, `--scan-interval` and `--dry-run`
+//! take precedence over the configurations, a given group or project replacing every configured
+//! group and project.
 //!
 extern crate alloc;
 
+#[macro_use]
+mod logging;
+
+mod audit;
+mod cli;
+mod commands;
+mod executor;
+mod jsonl;
+mod metrics;
+mod state;
+mod templates;
 mod tests;
 mod utils;
 
-use alloc::rc::Rc;
-use futures::stream::{self, StreamExt as _};
-use log::{error, info};
+use clap::Parser as _;
+use core::fmt::{self, Display};
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::process::ExitCode;
 use tokio::runtime;
-use tokio::time as tktime;
 
+use cli::{Cli, Command, ConfigCommand};
 use configloader::prelude::*;
 use gitlabapi::prelude::*;
-use mailsender::prelude::*;
 
 #[non_exhaustive]
 #[derive(Debug, Clone)]
 pub enum MailReason {
     Duplicated,
     InvalidTag,
+    /// The tag exists but breaks a configured tag rule.
+    TagRule(TagViolation),
+    /// The tag doesn't match any protected tag of the source project.
+    UnprotectedTag,
+    OutsideDeployWindow,
     ErrorToCancel,
     ErrorToPlay,
     MaxWaitElapsed,
+    /// Left manual because the job with this id, played before it in its concurrency group,
+    /// failed.
+    PreviousFailed(u64),
+    /// Canceled because this prerequisite job, from the `needs` deploy graph, didn't succeed.
+    PrerequisiteFailed(String),
     Status(JobScope),
 }
 
-fn main() -> Result<(), Box<dyn core::error::Error>> {
+impl MailReason {
+    /// Name of the reason, without its details.
+    #[inline]
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        match *self {
+            Self::Duplicated => "duplicated",
+            Self::InvalidTag => "invalid_tag",
+            Self::TagRule(_) => "tag_rule",
+            Self::UnprotectedTag => "unprotected_tag",
+            Self::OutsideDeployWindow => "outside_deploy_window",
+            Self::ErrorToCancel => "error_to_cancel",
+            Self::ErrorToPlay => "error_to_play",
+            Self::MaxWaitElapsed => "max_wait_elapsed",
+            Self::PreviousFailed(_) => "previous_failed",
+            Self::PrerequisiteFailed(_) => "prerequisite_failed",
+            Self::Status(_) => "status",
+        }
+    }
+
+    /// Details of the reason, like the broken tag rule or the failed job, if it has any.
+    #[inline]
+    #[must_use]
+    pub fn details(&self) -> Option<String> {
+        match self.clone() {
+            Self::TagRule(violation) => Some(violation.to_string()),
+            Self::PreviousFailed(previous) => Some(previous.to_string()),
+            Self::PrerequisiteFailed(prerequisite) => Some(prerequisite),
+            Self::Status(status) => Some(status.to_string()),
+            Self::Duplicated
+            | Self::InvalidTag
+            | Self::UnprotectedTag
+            | Self::OutsideDeployWindow
+            | Self::ErrorToCancel
+            | Self::ErrorToPlay
+            | Self::MaxWaitElapsed => None,
+        }
+    }
+}
+
+/// What to do with a manual job after its validation.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    Play,
+    Cancel,
+    /// Leave it in manual state for a later scan.
+    Defer,
+}
+
+impl Display for Decision {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Play => write!(f, "play"),
+            Self::Cancel => write!(f, "cancel"),
+            Self::Defer => write!(f, "defer"),
+        }
+    }
+}
+
+fn main() -> Result<ExitCode, Box<dyn core::error::Error>> {
+    let cli = Cli::parse();
+
     let rt = runtime::Builder::new_current_thread()
         .enable_time()
         .enable_io()
         .build()?;
 
-    rt.block_on(async {
-        // Set default log level to INFO, changed with "RUST_LOG" environment variable
-        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let exit_code = rt.block_on(async {
+        logging::init();
 
-        let config = match Config::load_config() {
+        let command = cli.command.unwrap_or_default();
+        let mut config = match Config::load_config() {
             Ok(conf) => conf,
-            Err(err) => {
-                error!("Error loading configurations. {}", err);
-                std::process::exit(1)
+            // Configurations that can't be loaded are the problem to report
+            Err(err)
+                if command
+                    == (Command::Config {
+                        command: ConfigCommand::Check,
+                    }) =>
+            {
+                return commands::check_config(Err(err));
             }
-        };
-
-        // Build mail relay
-        let smtp_configs = Rc::new(config.smtp.clone().unwrap_or_default());
-        let smtp_cfg = Rc::clone(&smtp_configs);
-
-        let mail_relay_handle =
-            tokio::task::spawn(utils::mailrelay_build(smtp_cfg.as_ref().to_owned()));
-
-        // Scan projects for Manual jobs
-        let api = GitlabJOB::new(&config);
-
-        let proj_jobs = match config.group_id {
-            Some(group_id) => api.get_jobs(GroupID(group_id), JobScope::Manual).await,
-            None => {
-                if let Some(proj_id) = config.project_id {
-                    api.get_jobs(ProjectID(proj_id), JobScope::Manual).await
-                } else {
-                    error!("There's no project to scan for jobs.");
-                    std::process::exit(2)
-                }
+            Err(err) => {
+                error!("Error loading configurations. {err}");
+                return ExitCode::FAILURE;
             }
         };
+        cli.overrides.apply(&mut config);
+        logging::set_format(config.log_format.unwrap_or_default());
 
-        log::info!(
-            "Projects with {} status jobs: {:?}",
-            JobScope::Manual,
-            proj_jobs.keys()
-        );
-
-        let verified_jobs = utils::validate_jobs(&api, &proj_jobs).await;
-
-        let actions = stream::iter(&verified_jobs)
-            .map(|(job, context)| {
-                if context.0 {
-                    api.play_job(job)
-                } else {
-                    api.cancel_job(job)
-                }
-            })
-            .buffer_unordered(STREAM_BUFF_SIZE)
-            .fuse()
-            .collect::<Vec<Result<&JobInfo, JobInfo>>>()
-            .await;
-
-        if !actions.is_empty() {
-            info!("All jobs were triggered. Now I'll wait theirs endings...");
-        }
-
-        // Prepare for mail reports
-        let mail_relay = match mail_relay_handle.await {
-            Ok(mailer) => {
-                debug!("Mail relay built");
-                mailer
+        match command {
+            Command::Run => commands::run(&config).await,
+            Command::List => commands::list(&config).await,
+            Command::Play { project, job } => {
+                commands::act(&config, project, job, Decision::Play).await
             }
-            Err(error) => {
-                error!("Error setting up mail relay: {}", error);
-                None
+            Command::Cancel { project, job } => {
+                commands::act(&config, project, job, Decision::Cancel).await
             }
-        };
-
-        // Which Gitlab status must be waited
-        let pending_status = [
-            JobScope::Pending,
-            JobScope::Running,
-            JobScope::WaitingForResource,
-            JobScope::Manual,
-        ];
-
-        // Stream to monitor jobs' status
-        let monitor_jobs = stream::iter(actions)
-            .map(|result| async {
-                match result {
-                    Ok(job) => {
-                        let cronometer = tktime::Instant::now();
-                        let max_wait =
-                            tktime::Duration::from_secs(config.max_wait_time.unwrap_or(30));
-                        let loop_wait_time = tktime::Duration::from_secs(10);
-
-                        loop {
-                            let curr_status = &api.get_status(job).await;
-
-                            if pending_status.contains(curr_status) {
-                                tktime::sleep(loop_wait_time).await;
-                                debug!("Waiting for job {}", job);
-                            } else {
-                                if let Some(mailer) = Option::as_ref(&mail_relay) {
-                                    let msg_reason = match *curr_status {
-                                        JobScope::Canceled => {
-                                            verified_jobs
-                                                .get(&job)
-                                                .unwrap_or(&(false, None))
-                                                .1
-                                                .as_ref()
-                                                .map_or(
-                                                    MailReason::Status(*curr_status),
-                                                    core::clone::Clone::clone
-                                                )
-                                        }
-                                        _ => MailReason::Status(*curr_status),
-                                    };
-
-                                    let mut job = job.clone();
-                                    job.status = Some(*curr_status);
-
-                                    //let smtp_configs = smtp_configs.clone();
-                                    let smtp_configs = Rc::<configloader::SmtpConfig>::clone(&smtp_configs);
-                                    let mailer = mailer.clone();
-
-                                    match mailer.send(&utils::mail_message(
-                                        &job,
-                                        &msg_reason,
-                                        smtp_configs.as_ref(),
-                                    )) {
-                                        Ok(_) => debug!("Message for job {} sent", &job),
-                                        Err(_) => {
-                                            error!("Erro while sneding message for job {}", &job);
-                                        }
-                                    };
-                                }
-
-                                info!("Job {} finished with status: {}", job, curr_status);
-                                break;
-                            }
-
-                            if cronometer.elapsed() >= max_wait {
-                                if let Some(mailer) = Option::as_ref(&mail_relay) {
-                                    let job = job.clone();
-                                    let smtp_configs =
-                                        Rc::<configloader::SmtpConfig>::clone(&smtp_configs);
-                                    let mailer = mailer.clone();
-
-                                    let _ = mailer.send(&utils::mail_message(
-                                        &job,
-                                        &MailReason::MaxWaitElapsed,
-                                        smtp_configs.as_ref(),
-                                    ));
-                                    match mailer.send(&utils::mail_message(
-                                        &job,
-                                        &MailReason::MaxWaitElapsed,
-                                        smtp_configs.as_ref(),
-                                    )) {
-                                        Ok(_) => debug!("Message for job {} sent", &job),
-                                        Err(_) => {
-                                            error!("Erro while sneding message for job {}", &job);
-                                        }
-                                    };
-                                }
-                                warn!("Job {} elapsed max waiting time", job);
-                                break;
-                            }
-                        }
-                    }
-                    Err(job) => {
-                        let message = verified_jobs.get(&job).map_or_else(
-                            || {
-                                unreachable!(
-                                    "Weird, some new job just appeared from nowhere: {}",
-                                    job
-                                )
-                            },
-                            |context| {
-                                let reason = if context.0 {
-                                    MailReason::ErrorToPlay
-                                } else {
-                                    MailReason::ErrorToCancel
-                                };
-                                utils::mail_message(&job, &reason, smtp_configs.as_ref())
-                            },
-                        );
-
-                        if let Some(mailer) = Option::as_ref(&mail_relay) {
-                            match mailer.send(&message) {
-                                Ok(res) => {
-                                    debug!("Sent mail for job {}: {}", job, res.code());
-                                }
-                                Err(error) => {
-                                    error!(
-                                        "Fail to send a email for job {}: {}\n{:?}",
-                                        job, error, message
-                                    );
-                                }
-                            };
-                        } else {
-                            error!("Fail to act on job {}: {:?}", job, message);
-                        }
-                    }
-                }
-            })
-            .buffer_unordered(STREAM_BUFF_SIZE)
-            .fuse();
-        tokio::pin!(monitor_jobs);
-
-        // Just another way to run streams
-        debug!("Wait emails sendings.");
-        while (monitor_jobs.next().await).is_some() {}
+            Command::Status { project, job } => commands::status(&config, project, job).await,
+            Command::History { job } => commands::history(&config, job),
+            Command::Config {
+                command: ConfigCommand::Check,
+            } => commands::check_config(Ok(&config)),
+        }
     });
     debug!("Bye!");
-    Ok(())
+    Ok(exit_code)
 }
@@ -0,0 +1,386 @@
+use alloc::sync::Arc;
+use core::convert::Infallible;
+use core::net::SocketAddr;
+use core::time::Duration;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::service::service_fn;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use log::{error, info};
+
+use gitlabapi::prelude::*;
+
+use crate::{Decision, MailReason};
+
+/// Upper bounds of the job wait buckets.
+const WAIT_BUCKETS: [Duration; 8] = [
+    Duration::from_secs(10),
+    Duration::from_secs(30),
+    Duration::from_mins(1),
+    Duration::from_mins(2),
+    Duration::from_mins(5),
+    Duration::from_mins(10),
+    Duration::from_mins(30),
+    Duration::from_hours(1),
+];
+
+/// Upper bounds of the Gitlab request latency buckets.
+const REQUEST_BUCKETS: [Duration; 8] = [
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_millis(2500),
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+];
+
+/// Metrics exposed, in exposition order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Family {
+    JobsDiscovered,
+    Decisions,
+    ActionFailures,
+    JobsFinished,
+    JobWait,
+    GitlabRequests,
+}
+
+impl Family {
+    const ALL: [Self; 6] = [
+        Self::JobsDiscovered,
+        Self::Decisions,
+        Self::ActionFailures,
+        Self::JobsFinished,
+        Self::JobWait,
+        Self::GitlabRequests,
+    ];
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::JobsDiscovered => "gitlabjobber_jobs_discovered",
+            Self::Decisions => "gitlabjobber_decisions_total",
+            Self::ActionFailures => "gitlabjobber_action_failures_total",
+            Self::JobsFinished => "gitlabjobber_jobs_finished_total",
+            Self::JobWait => "gitlabjobber_job_wait_seconds",
+            Self::GitlabRequests => "gitlabjobber_gitlab_request_duration_seconds",
+        }
+    }
+
+    const fn help(self) -> &'static str {
+        match self {
+            Self::JobsDiscovered => "Manual jobs found by the latest scan, by project.",
+            Self::Decisions => "Validation decisions, by decision and reason.",
+            Self::ActionFailures => "Play or cancel requests Gitlab refused or didn't answer.",
+            Self::JobsFinished => "Final statuses of the watched jobs.",
+            Self::JobWait => "Time spent watching a job until its final status or max wait time.",
+            Self::GitlabRequests => "Latency of Gitlab API requests, by endpoint and status.",
+        }
+    }
+
+    /// Prometheus type of the family.
+    const fn kind(self) -> &'static str {
+        match self {
+            Self::JobsDiscovered => "gauge",
+            Self::JobWait | Self::GitlabRequests => "histogram",
+            Self::Decisions | Self::ActionFailures | Self::JobsFinished => "counter",
+        }
+    }
+
+    /// Histogram buckets, `None` for counters and gauges.
+    const fn buckets(self) -> Option<&'static [Duration]> {
+        match self {
+            Self::JobWait => Some(&WAIT_BUCKETS),
+            Self::GitlabRequests => Some(&REQUEST_BUCKETS),
+            Self::JobsDiscovered | Self::Decisions | Self::ActionFailures | Self::JobsFinished => {
+                None
+            }
+        }
+    }
+}
+
+type Labels = Vec<(&'static str, String)>;
+
+/// Observations of a histogram series, with cumulative bucket counts.
+#[derive(Debug, Default)]
+struct Histogram {
+    buckets: Vec<u64>,
+    count: u64,
+    sum: Duration,
+}
+
+/// Counters, gauges and histograms of a run, rendered in the Prometheus text format.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Values of counters and gauges.
+    counters: Mutex<BTreeMap<(Family, Labels), u64>>,
+    histograms: Mutex<BTreeMap<(Family, Labels), Histogram>>,
+}
+
+impl Metrics {
+    /// Manual jobs found in each project by a scan, the projects of previous scans where none
+    /// were found going back to zero.
+    pub fn discovered<I>(&self, found: I)
+    where
+        I: IntoIterator<Item = (u64, usize)>,
+    {
+        if let Ok(mut counters) = self.counters.lock() {
+            for (key, value) in counters.iter_mut() {
+                if key.0 == Family::JobsDiscovered {
+                    *value = 0;
+                }
+            }
+            for (project, jobs) in found {
+                counters.insert(
+                    (
+                        Family::JobsDiscovered,
+                        vec![("project", project.to_string())],
+                    ),
+                    u64::try_from(jobs).unwrap_or(u64::MAX),
+                );
+            }
+        }
+    }
+
+    /// A job validation outcome.
+    pub fn decided(&self, decision: Decision, reason: Option<&MailReason>) {
+        self.add(
+            Family::Decisions,
+            vec![
+                ("decision", decision.to_string()),
+                ("reason", reason.map_or("none", MailReason::name).to_owned()),
+            ],
+            1,
+        );
+    }
+
+    /// A play or cancel request failed.
+    pub fn action_failed(&self, action: Decision) {
+        self.add(
+            Family::ActionFailures,
+            vec![("action", action.to_string())],
+            1,
+        );
+    }
+
+    /// A watched job reached a final status after `waited`.
+    pub fn finished(&self, status: JobScope, waited: Duration) {
+        self.add(
+            Family::JobsFinished,
+            vec![("status", status.to_string())],
+            1,
+        );
+        self.observe_duration(
+            Family::JobWait,
+            vec![("outcome", status.to_string())],
+            waited,
+        );
+    }
+
+    /// A watched job wasn't over within the max waiting time.
+    pub fn timed_out(&self, waited: Duration) {
+        self.observe_duration(
+            Family::JobWait,
+            vec![("outcome", "timed_out".to_owned())],
+            waited,
+        );
+    }
+
+    /// Every metric in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut lines = vec![];
+        let counters = self.counters.lock();
+        let histograms = self.histograms.lock();
+
+        for family in Family::ALL {
+            let mut series = vec![];
+            if let Ok(counters) = counters.as_ref() {
+                series.extend(counters.iter().filter(|entry| entry.0 .0 == family).map(
+                    |(key, value)| {
+                        format!("{}{} {value}", family.name(), render_labels(&key.1, None))
+                    },
+                ));
+            }
+            if let Ok(histograms) = histograms.as_ref() {
+                for (key, histogram) in histograms.iter().filter(|entry| entry.0 .0 == family) {
+                    series.extend(render_histogram(family, &key.1, histogram));
+                }
+            }
+
+            if !series.is_empty() {
+                lines.push(format!("# HELP {} {}", family.name(), family.help()));
+                lines.push(format!("# TYPE {} {}", family.name(), family.kind()));
+                lines.append(&mut series);
+            }
+        }
+
+        // Ends with a line break
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    /// Replace a node exporter textfile with the current metrics, through a temporary file so
+    /// the collector never reads it half written.
+    pub fn write_textfile(&self, path: &Path) -> io::Result<()> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        fs::write(&temporary, self.render())?;
+        fs::rename(&temporary, path)
+    }
+
+    /// Serve the metrics on `/metrics` until the task is dropped.
+    pub async fn serve(self: Arc<Self>, addr: SocketAddr) {
+        let listener = match tokio::net::TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(error) => {
+                error!("Couldn't listen for metrics on {addr}: {error}");
+                return;
+            }
+        };
+        info!("Serving metrics on http://{addr}/metrics");
+
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(error) => {
+                    error!("Stopped serving metrics: {error}");
+                    break;
+                }
+            };
+            let metrics = Arc::clone(&self);
+            tokio::spawn(async move {
+                let service = service_fn(move |request| {
+                    let response = metrics.answer(&request);
+                    async move { Ok::<_, Infallible>(response) }
+                });
+                if let Err(error) = hyper::server::conn::Http::new()
+                    .serve_connection(stream, service)
+                    .await
+                {
+                    debug!("Metrics connection closed: {error}");
+                }
+            });
+        }
+    }
+
+    fn answer(&self, request: &Request<Body>) -> Response<Body> {
+        if request.method() != Method::GET || request.uri().path() != "/metrics" {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::NOT_FOUND;
+            return response;
+        }
+
+        let mut response = Response::new(Body::from(self.render()));
+        response.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; version=0.0.4; charset=utf-8"),
+        );
+        response
+    }
+
+    fn add(&self, family: Family, labels: Labels, value: u64) {
+        if let Ok(mut counters) = self.counters.lock() {
+            let counter = counters.entry((family, labels)).or_default();
+            *counter = counter.saturating_add(value);
+        }
+    }
+
+    fn observe_duration(&self, family: Family, labels: Labels, value: Duration) {
+        let bounds = family.buckets().unwrap_or_default();
+        if let Ok(mut histograms) = self.histograms.lock() {
+            let histogram = histograms
+                .entry((family, labels))
+                .or_insert_with(|| Histogram {
+                    buckets: vec![0; bounds.len()],
+                    ..Histogram::default()
+                });
+            for (count, bound) in histogram.buckets.iter_mut().zip(bounds) {
+                if value <= *bound {
+                    *count = count.saturating_add(1);
+                }
+            }
+            histogram.count = histogram.count.saturating_add(1);
+            histogram.sum = histogram.sum.saturating_add(value);
+        }
+    }
+}
+
+impl RequestObserver for Metrics {
+    fn observe(&self, method: &str, endpoint: &str, status: Option<u16>, elapsed: Duration) {
+        self.observe_duration(
+            Family::GitlabRequests,
+            vec![
+                ("method", method.to_owned()),
+                ("endpoint", endpoint.to_owned()),
+                (
+                    "status",
+                    status.map_or_else(|| "error".to_owned(), |status| status.to_string()),
+                ),
+            ],
+            elapsed,
+        );
+    }
+}
+
+/// Lines of a histogram series, cumulative buckets first.
+fn render_histogram(family: Family, labels: &Labels, histogram: &Histogram) -> Vec<String> {
+    let name = family.name();
+    let bounds = family.buckets().unwrap_or_default();
+
+    let mut lines = histogram
+        .buckets
+        .iter()
+        .zip(bounds)
+        .map(|(count, bound)| {
+            let le = bound.as_secs_f64().to_string();
+            format!(
+                "{name}_bucket{} {count}",
+                render_labels(labels, Some(("le", &le)))
+            )
+        })
+        .collect::<Vec<_>>();
+    lines.push(format!(
+        "{name}_bucket{} {}",
+        render_labels(labels, Some(("le", "+Inf"))),
+        histogram.count
+    ));
+    lines.push(format!(
+        "{name}_sum{} {}",
+        render_labels(labels, None),
+        histogram.sum.as_secs_f64()
+    ));
+    lines.push(format!(
+        "{name}_count{} {}",
+        render_labels(labels, None),
+        histogram.count
+    ));
+    lines
+}
+
+/// Labels between braces, with their values escaped, empty when there's none.
+fn render_labels(labels: &Labels, extra: Option<(&str, &str)>) -> String {
+    let pairs = labels
+        .iter()
+        .map(|label| (label.0, label.1.as_str()))
+        .chain(extra)
+        .map(|(name, value)| {
+            let escaped = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{name}=\"{escaped}\"")
+        })
+        .collect::<Vec<_>>();
+
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
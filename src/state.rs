@@ -0,0 +1,242 @@
+use core::fmt::{self, Display};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufRead as _, BufReader};
+use std::path::Path;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use gitlabapi::prelude::*;
+
+use crate::jsonl::JsonLines;
+use crate::Decision;
+
+/// Something done to a job, or seen from it.
+#[non_exhaustive]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// Outcome of the job validation.
+    Decided {
+        decision: Decision,
+        reason: Option<String>,
+    },
+    /// Answer to the play request, with its error when it failed, or until when the job is
+    /// watched when it succeeded.
+    Played {
+        error: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        deadline: Option<DateTime<Utc>>,
+    },
+    /// Answer to the cancel request, with its error when it failed.
+    Canceled { error: Option<String> },
+    /// Final status seen while monitoring the job.
+    Finished { status: String },
+    /// The job wasn't over within the max waiting time.
+    TimedOut,
+    /// Left manual because this job, played before it in its concurrency group, failed.
+    Skipped { after: u64 },
+}
+
+impl Event {
+    /// Whether the job was acted on or is over, so later runs leave it alone.
+    const fn is_handled(&self) -> bool {
+        match *self {
+            Self::Played { error: None, .. }
+            | Self::Canceled { error: None }
+            | Self::Finished { .. }
+            | Self::TimedOut
+            | Self::Skipped { .. } => true,
+            Self::Played { error: Some(_), .. }
+            | Self::Canceled { error: Some(_) }
+            | Self::Decided { .. } => false,
+        }
+    }
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.clone() {
+            Self::Decided {
+                decision,
+                reason: Some(reason),
+            } => write!(f, "decided {decision}: {reason}"),
+            Self::Decided {
+                decision,
+                reason: None,
+            } => write!(f, "decided {decision}"),
+            Self::Played { error: None, .. } => write!(f, "played"),
+            Self::Played {
+                error: Some(error), ..
+            } => write!(f, "play failed: {error}"),
+            Self::Canceled { error: None } => write!(f, "canceled"),
+            Self::Canceled { error: Some(error) } => write!(f, "cancel failed: {error}"),
+            Self::Finished { status } => write!(f, "finished with status {status}"),
+            Self::TimedOut => write!(f, "max wait time elapsed"),
+            Self::Skipped { after } => write!(f, "left manual, job {after} failed before it"),
+        }
+    }
+}
+
+/// A line of the state file.
+#[non_exhaustive]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub at: DateTime<Utc>,
+    pub job_id: u64,
+    pub project_id: Option<u64>,
+    pub pipeline_id: Option<u64>,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+impl Record {
+    /// The job of the record, with the fields the state file keeps.
+    pub fn job(&self) -> JobInfo {
+        JobInfo {
+            id: Some(self.job_id),
+            proj_id: self.project_id,
+            pipeline_id: self.pipeline_id,
+            ..Default::default()
+        }
+    }
+}
+
+/// Append-only file of JSON lines recording what happened to every handled job.
+pub struct StateStore {
+    file: JsonLines,
+    /// Ids of jobs acted on or over, in this run or a previous one.
+    handled: Mutex<HashSet<u64>>,
+    /// Last decision recorded for each job by previous runs.
+    decided: HashMap<u64, Event>,
+}
+
+impl StateStore {
+    /// Open a state file, reading the jobs handled by previous runs.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut handled = HashSet::new();
+        let mut decided = HashMap::new();
+        for record in Self::read(path)? {
+            if record.event.is_handled() {
+                handled.insert(record.job_id);
+            }
+            if let Event::Decided { .. } = record.event {
+                decided.insert(record.job_id, record.event);
+            }
+        }
+
+        Ok(Self {
+            file: JsonLines::new(path.to_path_buf()),
+            handled: Mutex::new(handled),
+            decided,
+        })
+    }
+
+    /// Every record of the state file, a missing file having none.
+    pub fn read(path: &Path) -> io::Result<Vec<Record>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(error) => return Err(error),
+        };
+
+        let mut records = vec![];
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // A line cut by a crash must not hide the other ones
+            match serde_json::from_str(&line) {
+                Ok(record) => records.push(record),
+                Err(error) => error!(
+                    "Skipping line {} of state file {}: {error}",
+                    number.saturating_add(1),
+                    path.display()
+                ),
+            }
+        }
+        Ok(records)
+    }
+
+    /// Records of a single job in a state file, oldest first.
+    pub fn history(path: &Path, job_id: u64) -> io::Result<Vec<Record>> {
+        Ok(Self::read(path)?
+            .into_iter()
+            .filter(|record| record.job_id == job_id)
+            .collect())
+    }
+
+    /// Jobs played whose final status wasn't seen, with the deadline to watch them.
+    ///
+    /// Played records without deadline are checked a single time.
+    pub fn in_flight(&self) -> io::Result<Vec<(Record, DateTime<Utc>)>> {
+        let mut played: HashMap<u64, (Record, DateTime<Utc>)> = HashMap::new();
+
+        for record in Self::read(self.file.path())? {
+            match record.event {
+                Event::Played {
+                    error: None,
+                    deadline,
+                } => {
+                    let deadline = deadline.unwrap_or(record.at);
+                    played.insert(record.job_id, (record, deadline));
+                }
+                Event::Finished { .. } | Event::TimedOut | Event::Canceled { error: None } => {
+                    played.remove(&record.job_id);
+                }
+                Event::Played { error: Some(_), .. }
+                | Event::Canceled { error: Some(_) }
+                | Event::Decided { .. }
+                | Event::Skipped { .. } => {}
+            }
+        }
+
+        let mut played = played.into_values().collect::<Vec<_>>();
+        played.sort_by_key(|entry| entry.0.at);
+        Ok(played)
+    }
+
+    /// Last decision recorded for each job when the file was opened.
+    pub const fn decisions(&self) -> &HashMap<u64, Event> {
+        &self.decided
+    }
+
+    /// Whether a job was acted on or is over, so it's left alone.
+    pub fn is_handled(&self, job_id: u64) -> bool {
+        self.handled
+            .lock()
+            .is_ok_and(|handled| handled.contains(&job_id))
+    }
+
+    /// Append an event of a job, logging failures.
+    pub fn record(&self, job: &JobInfo, event: Event) {
+        let Some(job_id) = job.id else {
+            return;
+        };
+        if event.is_handled() {
+            if let Ok(mut handled) = self.handled.lock() {
+                handled.insert(job_id);
+            }
+        }
+
+        let record = Record {
+            at: Utc::now(),
+            job_id,
+            project_id: job.proj_id,
+            pipeline_id: job.pipeline_id,
+            event,
+        };
+        if let Err(error) = self.file.append(&record) {
+            job_log!(
+                Error,
+                job,
+                "Couldn't record job {job} in state file {}: {error}",
+                self.file.path().display()
+            );
+        }
+    }
+}
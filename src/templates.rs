@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use log::error;
+use minijinja::{Environment, Value};
+
+use configloader::prelude::*;
+use gitlabapi::prelude::*;
+
+use crate::{utils, MailReason};
+
+/// Reasons templates can be set for, as named by `MailReason::name`.
+pub const REASONS: [&str; 11] = [
+    "duplicated",
+    "invalid_tag",
+    "tag_rule",
+    "unprotected_tag",
+    "outside_deploy_window",
+    "error_to_cancel",
+    "error_to_play",
+    "max_wait_elapsed",
+    "previous_failed",
+    "prerequisite_failed",
+    "status",
+];
+
+/// Parts of a mail a template can replace.
+#[derive(Debug, Clone, Copy)]
+enum Part {
+    Subject,
+    Html,
+    Text,
+}
+
+impl Part {
+    const ALL: [Self; 3] = [Self::Subject, Self::Html, Self::Text];
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Subject => "subject",
+            Self::Html => "html",
+            Self::Text => "text",
+        }
+    }
+
+    /// Template name of the part for a reason, its extension turning html escaping on.
+    fn template(self, reason: &str) -> String {
+        let extension = match self {
+            Self::Subject => "subject",
+            Self::Html => "html",
+            Self::Text => "txt",
+        };
+        format!("{reason}.{extension}")
+    }
+
+    const fn path(self, template: &MailTemplate) -> Option<&String> {
+        match self {
+            Self::Subject => template.subject.as_ref(),
+            Self::Html => template.html.as_ref(),
+            Self::Text => template.text.as_ref(),
+        }
+    }
+}
+
+/// Subject and body templates read from the `smtp.templates` files, by mail reason.
+pub struct MailTemplates {
+    env: Environment<'static>,
+}
+
+impl MailTemplates {
+    /// Read the configured templates, logging the ones left out.
+    pub fn new(smtp: &SmtpConfig) -> Self {
+        let (templates, problems) = Self::load(smtp);
+        for problem in problems {
+            error!("{problem}, using the built-in one");
+        }
+        templates
+    }
+
+    /// Problems of the configured templates, unreadable or invalid.
+    pub fn check(smtp: &SmtpConfig) -> Vec<String> {
+        Self::load(smtp).1
+    }
+
+    fn load(smtp: &SmtpConfig) -> (Self, Vec<String>) {
+        let mut env = Environment::new();
+        let mut problems = vec![];
+
+        let configured = smtp.templates.iter().flatten().collect::<BTreeMap<_, _>>();
+        for (reason, template) in configured {
+            if !REASONS.contains(&reason.as_str()) {
+                problems.push(format!(
+                    "smtp templates of unknown mail reason \"{reason}\""
+                ));
+                continue;
+            }
+            for part in Part::ALL {
+                let Some(path) = part.path(template) else {
+                    continue;
+                };
+                let added = fs::read_to_string(path)
+                    .map_err(|error| error.to_string())
+                    .and_then(|source| {
+                        env.add_template_owned(part.template(reason), source)
+                            .map_err(|error| error.to_string())
+                    });
+                if let Err(error) = added {
+                    problems.push(format!(
+                        "smtp {} template {path} of {reason} can't be used: {error}",
+                        part.name()
+                    ));
+                }
+            }
+        }
+
+        (Self { env }, problems)
+    }
+
+    /// Mail subject from the template of the reason, if any.
+    pub fn subject(&self, job: &JobInfo, reason: &MailReason) -> Option<String> {
+        self.render(Part::Subject, job, reason)
+            .map(|subject| subject.trim().to_owned())
+            .filter(|subject| !subject.is_empty())
+    }
+
+    /// Html mail body from the template of the reason, if any.
+    pub fn html(&self, job: &JobInfo, reason: &MailReason) -> Option<String> {
+        self.render(Part::Html, job, reason)
+    }
+
+    /// Plain text mail body from the template of the reason, if any.
+    pub fn text(&self, job: &JobInfo, reason: &MailReason) -> Option<String> {
+        self.render(Part::Text, job, reason)
+    }
+
+    /// A part rendered from its template, `None` when there's none or it fails.
+    fn render(&self, part: Part, job: &JobInfo, reason: &MailReason) -> Option<String> {
+        let template = self.env.get_template(&part.template(reason.name())).ok()?;
+        match template.render(context(job, reason)) {
+            Ok(rendered) => Some(rendered),
+            Err(error) => {
+                job_log!(
+                    Error,
+                    job,
+                    "Couldn't render the {} template of job {job}: {error}",
+                    part.name()
+                );
+                None
+            }
+        }
+    }
+}
+
+/// Values given to templates: every known job field, the job as shown in the built-in
+/// mails, the reason name and its details, and the built-in subject as `summary`.
+fn context(job: &JobInfo, reason: &MailReason) -> BTreeMap<&'static str, Value> {
+    let numbers = [
+        ("id", job.id),
+        ("proj_id", job.proj_id),
+        ("pipeline_id", job.pipeline_id),
+        ("source_id", job.source_id),
+    ];
+    let texts = [
+        ("name", job.name.as_ref()),
+        ("stage", job.stage.as_ref()),
+        ("environment", job.environment.as_ref()),
+        ("url", job.url.as_ref()),
+        ("proj_name", job.proj_name.as_ref()),
+        ("user_mail", job.user_mail.as_ref()),
+        ("branch", job.branch.as_ref()),
+        ("git_tag", job.git_tag.as_ref()),
+    ];
+    // Unknown fields are left undefined, for `default` and `if` to handle
+    let mut context = numbers
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, Value::from(value?))))
+        .chain(
+            texts
+                .into_iter()
+                .filter_map(|(key, value)| Some((key, Value::from(value?.as_str())))),
+        )
+        .collect::<BTreeMap<_, _>>();
+    if let Some(status) = job.status {
+        context.insert("status", Value::from(status.to_string()));
+    }
+    if let Some(details) = reason.details() {
+        context.insert("details", Value::from(details));
+    }
+    context.insert("job", Value::from(job.to_string()));
+    context.insert("reason", Value::from(reason.name()));
+    context.insert("summary", Value::from(utils::mail_subject(job, reason)));
+    context
+}
@@ -1,10 +1,27 @@
 #[cfg(test)]
 mod integration_tests {
+    use crate::audit::{AuditEvent, AuditLog};
+    use crate::executor::Executor;
+    use crate::logging::{self, JobContext};
+    use crate::metrics::Metrics;
+    use crate::state::{Event, StateStore};
+    use crate::templates::{self, MailTemplates};
     use crate::*;
+    use alloc::sync::Arc;
+    use chrono::Utc;
+    use configloader::{JobPattern, PatternFilter};
+    use gitlabapi::mock::{Method, MockGitlab, MockJob, StatusCode};
+    use mailsender::prelude::*;
+    use std::collections::HashMap;
     // use std::io::Write;
     use log::debug;
     use std::process::exit;
 
+    #[expect(
+        clippy::let_underscore_must_use,
+        clippy::let_underscore_untyped,
+        reason = "Logger may be set by another test"
+    )]
     fn init() {
         let _ = env_logger::builder()
             .filter_level(log::LevelFilter::max())
@@ -14,6 +31,14 @@ mod integration_tests {
 
     #[tokio::test]
     #[ignore = "pipeline cration"]
+    #[expect(
+        clippy::exit,
+        clippy::manual_let_else,
+        clippy::option_if_let_else,
+        clippy::single_match_else,
+        clippy::uninlined_format_args,
+        reason = "Kept as written before the lint rules"
+    )]
     async fn create_pipeline() {
         // TESTE_TOKENTRIG="token" cargo test --package gitlabjob --bin gitlabjob -- tests::integration_tests::create_pipeline \
         // --exact --nocapture --ignored
@@ -61,35 +86,1587 @@ mod integration_tests {
         }
     }
 
+    /// Fake Gitlab with a group of projects whose manual jobs get every decision.
+    async fn gitlab() -> MockGitlab {
+        init();
+        let gitlab = MockGitlab::start().await;
+        gitlab.add_group(1, &[20, 30, 40]);
+
+        // Older pipeline duplicated by a newer one with a valid tag
+        gitlab.add_project(20, "backend");
+        gitlab.add_tags(20, &["v1.2.0"]);
+        gitlab.add_job(20, MockJob::manual(200, 2000));
+        gitlab.add_job(
+            20,
+            MockJob {
+                git_ref: "v1.2.0".to_owned(),
+                ..MockJob::manual(201, 2001)
+            },
+        );
+
+        // Unknown tag
+        gitlab.add_project(30, "frontend");
+        gitlab.add_tags(30, &["v1.0.0"]);
+        gitlab.add_job(
+            30,
+            MockJob {
+                git_ref: "v9.9.9".to_owned(),
+                ..MockJob::manual(300, 3000)
+            },
+        );
+
+        // Tags can't be read
+        gitlab.add_project(40, "worker");
+        gitlab.add_job(
+            40,
+            MockJob {
+                git_ref: "v2.0.0".to_owned(),
+                ..MockJob::manual(400, 4000)
+            },
+        );
+
+        gitlab
+    }
+
+    fn config(gitlab: &MockGitlab) -> Config {
+        Config {
+            group_id: Some(1),
+            ..gitlab.config()
+        }
+    }
+
     #[tokio::test]
+    #[expect(
+        clippy::redundant_test_prefix,
+        reason = "Kept as written before the lint rules"
+    )]
     async fn test_pipelines_to_cancel() {
-        init();
+        let gitlab = gitlab().await;
+        let api = GitlabJOB::new(&config(&gitlab));
 
-        let config = Config::load_config().unwrap();
+        let response = api.get_jobs(ProjectID(20), JobScope::Manual).await.unwrap();
+        let to_cancel = utils::duplicated_jobs(&api.config, &response)
+            .iter()
+            .map(|job| job.id)
+            .collect::<Vec<_>>();
 
-        let api = GitlabJOB::new(&config);
-        let proj = ProjectID(config.project_id.unwrap());
+        assert_eq!(
+            to_cancel,
+            vec![Some(200)],
+            "Only the older pipeline must be canceled"
+        );
+    }
+
+    #[tokio::test]
+    async fn dedup_strategies() {
+        let gitlab = gitlab().await;
+        // A newer pipeline deploying an older version
+        gitlab.add_project(70, "gateway");
+        gitlab.add_job(
+            70,
+            MockJob {
+                git_ref: "v2.0.0".to_owned(),
+                environment: Some("production".to_owned()),
+                ..MockJob::manual(700, 7000)
+            },
+        );
+        gitlab.add_job(
+            70,
+            MockJob {
+                git_ref: "v1.9.0".to_owned(),
+                environment: Some("staging".to_owned()),
+                ..MockJob::manual(701, 7001)
+            },
+        );
+        let api = GitlabJOB::new(&config(&gitlab));
+        let jobs = api.get_jobs(ProjectID(70), JobScope::Manual).await.unwrap();
+
+        let canceled = |dedup: DedupConfig| {
+            let config = Config {
+                dedup: Some(dedup),
+                ..config(&gitlab)
+            };
+            utils::duplicated_jobs(&config, &jobs)
+                .iter()
+                .filter_map(|job| job.id)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            canceled(DedupConfig::default()),
+            [700],
+            "Older pipeline must be canceled by default"
+        );
+        assert_eq!(
+            canceled(DedupConfig {
+                keep: Some(DedupKeep::Semver),
+                ..Default::default()
+            }),
+            [701],
+            "Older version must be canceled"
+        );
+        assert!(
+            canceled(DedupConfig {
+                key: Some(DedupKey::Environment),
+                ..Default::default()
+            })
+            .is_empty(),
+            "Jobs of different environments aren't duplicates"
+        );
+        assert_eq!(
+            canceled(DedupConfig {
+                key: Some(DedupKey::JobName),
+                keep: Some(DedupKeep::Semver),
+            }),
+            [701],
+            "Jobs with the same name are duplicates"
+        );
+
+        // Jobs deploying to no environment
+        gitlab.add_project(71, "scheduler");
+        gitlab.add_job(71, MockJob::manual(710, 7100));
+        gitlab.add_job(71, MockJob::manual(711, 7101));
+        let unkeyed = api.get_jobs(ProjectID(71), JobScope::Manual).await.unwrap();
+        let config = Config {
+            dedup: Some(DedupConfig {
+                key: Some(DedupKey::Environment),
+                ..Default::default()
+            }),
+            ..config(&gitlab)
+        };
+        assert!(
+            utils::duplicated_jobs(&config, &unkeyed).is_empty(),
+            "Jobs without the keyed field aren't duplicates"
+        );
+    }
+
+    #[tokio::test]
+    async fn validate_jobs() {
+        let gitlab = gitlab().await;
+        gitlab.fail(
+            Method::GET,
+            "/api/v4/projects/40/repository/tags",
+            StatusCode::INTERNAL_SERVER_ERROR,
+            3,
+        );
+        // Pre-release refused by the project tag rules
+        gitlab.add_project(50, "scheduler");
+        gitlab.add_tags(50, &["v1.3.0-rc.1"]);
+        gitlab.add_job(
+            50,
+            MockJob {
+                git_ref: "v1.3.0-rc.1".to_owned(),
+                ..MockJob::manual(500, 5000)
+            },
+        );
+        gitlab.add_group(1, &[20, 30, 40, 50]);
+        let api = GitlabJOB::new(&Config {
+            projects: Some(HashMap::from([(
+                "50".to_owned(),
+                ProjectConfig {
+                    tags: Some(TagRules {
+                        reject_prerelease: Some(true),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            )])),
+            ..config(&gitlab)
+        });
 
-        let response = api.get_jobs(proj, JobScope::Manual).await;
+        let proj_jobs = api.get_jobs(GroupID(1), JobScope::Manual).await.unwrap();
+        let verified_jobs = utils::validate_jobs(&api, &proj_jobs).await;
 
-        response.iter().for_each(|(project, jobs)| {
-            debug!("Project {} has {} pipelines.", project.0, jobs.len())
+        let decision = |id: u64| {
+            verified_jobs
+                .iter()
+                .find(|&(job, _)| job.id == Some(id))
+                .map(|(_, context)| context)
+                .unwrap()
+        };
+        assert!(
+            matches!(
+                decision(200),
+                (Decision::Cancel, Some(MailReason::Duplicated))
+            ),
+            "Duplicated pipeline must be canceled"
+        );
+        assert!(
+            matches!(decision(201), (Decision::Play, None)),
+            "Valid job must be played"
+        );
+        assert!(
+            matches!(
+                decision(300),
+                (Decision::Cancel, Some(MailReason::InvalidTag))
+            ),
+            "Unknown tag must be canceled"
+        );
+        assert!(
+            matches!(decision(400), (Decision::Defer, None)),
+            "Unchecked tag must be deferred"
+        );
+        assert!(
+            matches!(
+                decision(500),
+                (
+                    Decision::Cancel,
+                    Some(MailReason::TagRule(TagViolation::Prerelease))
+                )
+            ),
+            "Pre-release tag must be canceled"
+        );
+    }
+
+    #[tokio::test]
+    async fn protected_tags() {
+        let gitlab = gitlab().await;
+        gitlab.protect_tag(20, "v1.*");
+        gitlab.add_project(60, "api");
+        gitlab.protect_tag(60, "v1.*");
+        gitlab.add_tags(60, &["v2.0.0"]);
+        gitlab.add_job(
+            60,
+            MockJob {
+                git_ref: "v2.0.0".to_owned(),
+                ..MockJob::manual(600, 6000)
+            },
+        );
+        let api = GitlabJOB::new(&Config {
+            tags: Some(TagRules {
+                require_protected: Some(true),
+                ..Default::default()
+            }),
+            ..config(&gitlab)
         });
 
-        debug!("Got {} projects", response.values().len());
-        let to_cancel = utils::pipelines_tocancel(&response);
-        to_cancel.iter().for_each(|(proj, pipes)| {
-            debug!(
-                "For project {}, {} jobs will be canceled",
-                proj.0,
-                pipes.len()
-            )
+        let mut proj_jobs = api.get_jobs(ProjectID(20), JobScope::Manual).await.unwrap();
+        proj_jobs.extend(api.get_jobs(ProjectID(60), JobScope::Manual).await.unwrap());
+        let verified_jobs = utils::validate_jobs(&api, &proj_jobs).await;
+
+        let decision = |id: u64| {
+            verified_jobs
+                .iter()
+                .find(|&(job, _)| job.id == Some(id))
+                .map(|(_, context)| context)
+                .unwrap()
+        };
+        assert!(
+            matches!(decision(201), (Decision::Play, None)),
+            "Protected tag must be played"
+        );
+        assert!(
+            matches!(
+                decision(600),
+                (Decision::Cancel, Some(MailReason::UnprotectedTag))
+            ),
+            "Unprotected tag must be canceled"
+        );
+
+        gitlab.fail(
+            Method::GET,
+            "/api/v4/projects/60/protected_tags",
+            StatusCode::FORBIDDEN,
+            1,
+        );
+        let deferred_jobs = utils::validate_jobs(&api, &proj_jobs).await;
+        assert!(
+            deferred_jobs
+                .iter()
+                .any(|(job, context)| job.id == Some(600)
+                    && matches!(context, (Decision::Defer, None))),
+            "Jobs must be deferred when protected tags can't be read"
+        );
+
+        // Without the tag variable in their pipeline, jobs have no tag to check
+        let untagged_api = GitlabJOB::new(&Config {
+            production_tag_key: Some("PROD_TAG".to_owned()),
+            ..api.config.clone()
         });
-        debug!("{:?}", to_cancel);
+        let untagged = untagged_api
+            .get_jobs(ProjectID(60), JobScope::Manual)
+            .await
+            .unwrap();
+        assert!(
+            untagged.values().flatten().all(|job| job.git_tag.is_none()),
+            "Jobs mustn't have a tag"
+        );
+        let untagged_jobs = utils::validate_jobs(&untagged_api, &untagged).await;
+        assert!(
+            !untagged_jobs.is_empty()
+                && untagged_jobs.values().all(|context| matches!(
+                    context,
+                    (Decision::Cancel, Some(MailReason::UnprotectedTag))
+                )),
+            "Jobs without tag must be canceled when protected tags are required"
+        );
+    }
+
+    #[tokio::test]
+    async fn monitor_jobs() {
+        let gitlab = gitlab().await;
+        // Stays running once played, for longer than the max waiting time
+        gitlab.add_project(50, "scheduler");
+        gitlab.add_tags(50, &["v3.0.0"]);
+        gitlab.add_job(
+            50,
+            MockJob {
+                git_ref: "v3.0.0".to_owned(),
+                after_play: JobScope::Running,
+                ..MockJob::manual(500, 5000)
+            },
+        );
+        gitlab.add_group(1, &[20, 30, 40, 50]);
+
+        let executor = Arc::new(Executor::new(
+            &Config {
+                max_wait_time: Some(0),
+                ..config(&gitlab)
+            },
+            None,
+        ));
+        executor.run_once().await;
+
+        assert_eq!(
+            gitlab.job_status(20, 200),
+            Some(JobScope::Canceled),
+            "Duplicated job wasn't canceled"
+        );
+        assert_eq!(
+            gitlab.job_status(20, 201),
+            Some(JobScope::Success),
+            "Valid job wasn't played"
+        );
+        assert_eq!(
+            gitlab.job_status(30, 300),
+            Some(JobScope::Canceled),
+            "Job with unknown tag wasn't canceled"
+        );
+        assert_eq!(
+            gitlab.job_status(50, 500),
+            Some(JobScope::Running),
+            "Long job wasn't played"
+        );
+
+        // Acted jobs were monitored until their end or the max waiting time
+        let requests = gitlab.requests();
+        for job in ["20/jobs/200", "20/jobs/201", "30/jobs/300", "50/jobs/500"] {
+            let path = format!("GET /api/v4/projects/{job}");
+            assert!(
+                requests.iter().filter(|&request| request == &path).count() >= 2,
+                "Job {job} wasn't monitored"
+            );
+        }
+        assert!(
+            !requests.contains(&"POST /api/v4/projects/40/jobs/400/play".to_owned()),
+            "Deferred job was played"
+        );
+    }
+
+    #[tokio::test]
+    async fn concurrency_groups() {
+        init();
+        let gitlab = MockGitlab::start().await;
+        for (project, job, pipeline, after_play) in [
+            (80, 800, 8000, JobScope::Success),
+            (81, 810, 8050, JobScope::Failed),
+            (80, 801, 8100, JobScope::Success),
+            (82, 820, 8200, JobScope::Success),
+        ] {
+            gitlab.add_project(project, "service");
+            gitlab.add_tags(project, &["v1.0.0"]);
+            gitlab.add_job(
+                project,
+                MockJob {
+                    name: format!("deploy-{job}"),
+                    git_ref: "v1.0.0".to_owned(),
+                    after_play,
+                    ..MockJob::manual(job, pipeline)
+                },
+            );
+        }
+
+        let executor = Arc::new(Executor::new(
+            &Config {
+                project_ids: Some(vec![80, 81, 82]),
+                dedup: Some(DedupConfig {
+                    key: Some(DedupKey::JobName),
+                    ..Default::default()
+                }),
+                concurrency: Some(ConcurrencyConfig {
+                    per_project: Some(true),
+                    groups: Some(HashMap::from([("shared".to_owned(), vec![80, 81])])),
+                }),
+                max_wait_time: Some(0),
+                ..gitlab.config()
+            },
+            None,
+        ));
+        executor.run_once().await;
+
+        let requests = gitlab.requests();
+        let position = |request: &str| requests.iter().rposition(|sent| sent == request);
+        assert!(
+            position("GET /api/v4/projects/80/jobs/800")
+                < position("POST /api/v4/projects/81/jobs/810/play"),
+            "Job must wait the end of the previous one in its group"
+        );
+        assert_eq!(
+            gitlab.job_status(81, 810),
+            Some(JobScope::Failed),
+            "Second job of the group wasn't played"
+        );
+        assert_eq!(
+            gitlab.job_status(80, 801),
+            Some(JobScope::Manual),
+            "Job after a failed one wasn't left manual"
+        );
+        assert_eq!(
+            position("POST /api/v4/projects/80/jobs/801/cancel"),
+            None,
+            "Job after a failed one was canceled"
+        );
+
+        executor.run_once().await;
+        assert_eq!(
+            gitlab
+                .requests()
+                .iter()
+                .position(|sent| sent == "POST /api/v4/projects/80/jobs/801/play"),
+            None,
+            "Job after a failed one was played"
+        );
+        assert_eq!(
+            gitlab.job_status(82, 820),
+            Some(JobScope::Success),
+            "Job of its own project group wasn't played"
+        );
+    }
+
+    #[tokio::test]
+    async fn deploy_graph_decisions() {
+        let gitlab = gitlab().await;
+        gitlab.fail(
+            Method::GET,
+            "/api/v4/projects/40/repository/tags",
+            StatusCode::INTERNAL_SERVER_ERROR,
+            6,
+        );
+        let scanned = GitlabJOB::new(&config(&gitlab))
+            .get_jobs(GroupID(1), JobScope::Manual)
+            .await
+            .unwrap();
+        let proj_jobs = &scanned;
+        let decision = |needs: &str| {
+            let api = GitlabJOB::new(&Config {
+                needs: Some(DeployGraph(HashMap::from([(
+                    "20".to_owned(),
+                    vec![needs.to_owned()],
+                )]))),
+                ..config(&gitlab)
+            });
+            async move {
+                utils::validate_jobs(&api, proj_jobs)
+                    .await
+                    .into_iter()
+                    .find(|&(job, _)| job.id == Some(201))
+                    .map(|(_, context)| context)
+                    .unwrap()
+            }
+        };
+
+        assert!(
+            matches!(
+                decision("30").await,
+                (Decision::Cancel, Some(MailReason::PrerequisiteFailed(_)))
+            ),
+            "Job needing a canceled one must be canceled"
+        );
+        assert!(
+            matches!(decision("40").await, (Decision::Defer, None)),
+            "Job needing a deferred one must be deferred"
+        );
+    }
+
+    #[tokio::test]
+    async fn deploy_graph() {
+        init();
+        // Migration, then backend, then frontend, the backend failing
+        let gitlab = MockGitlab::start().await;
+        for (project, after_play) in [
+            (10, JobScope::Success),
+            (11, JobScope::Failed),
+            (12, JobScope::Success),
+        ] {
+            gitlab.add_project(project, "service");
+            gitlab.add_tags(project, &["v1.0.0"]);
+            gitlab.add_job(
+                project,
+                MockJob {
+                    git_ref: "v1.0.0".to_owned(),
+                    after_play,
+                    ..MockJob::manual(project * 10, project * 100)
+                },
+            );
+        }
+        let executor = Arc::new(Executor::new(
+            &Config {
+                project_ids: Some(vec![10, 11, 12]),
+                needs: Some(DeployGraph(HashMap::from([
+                    ("11".to_owned(), vec!["10".to_owned()]),
+                    ("12".to_owned(), vec!["11".to_owned()]),
+                ]))),
+                max_wait_time: Some(0),
+                ..gitlab.config()
+            },
+            None,
+        ));
+        executor.run_once().await;
+
+        let requests = gitlab.requests();
+        let position = |request: &str| requests.iter().rposition(|sent| sent == request);
+        assert!(
+            position("GET /api/v4/projects/10/jobs/100")
+                < position("POST /api/v4/projects/11/jobs/110/play"),
+            "Job must wait the end of its prerequisite"
+        );
+        assert_eq!(
+            gitlab.job_status(11, 110),
+            Some(JobScope::Failed),
+            "Job after its succeeded prerequisite wasn't played"
+        );
+        assert_eq!(
+            gitlab.job_status(12, 120),
+            Some(JobScope::Canceled),
+            "Job after its failed prerequisite wasn't canceled"
+        );
+    }
+
+    #[tokio::test]
+    async fn deploy_graph_cycle() {
+        init();
+        let gitlab = MockGitlab::start().await;
+        for project in [13, 14] {
+            gitlab.add_project(project, "service");
+            gitlab.add_tags(project, &["v1.0.0"]);
+            gitlab.add_job(
+                project,
+                MockJob {
+                    git_ref: "v1.0.0".to_owned(),
+                    after_play: JobScope::Success,
+                    ..MockJob::manual(project * 10, project * 100)
+                },
+            );
+        }
+        let executor = Arc::new(Executor::new(
+            &Config {
+                project_ids: Some(vec![13, 14]),
+                needs: Some(DeployGraph(HashMap::from([
+                    ("13".to_owned(), vec!["14".to_owned()]),
+                    ("14".to_owned(), vec!["13".to_owned()]),
+                ]))),
+                max_wait_time: Some(0),
+                ..gitlab.config()
+            },
+            None,
+        ));
+        executor.run_once().await;
+
+        for (project, job) in [(13, 130), (14, 140)] {
+            assert_eq!(
+                gitlab.job_status(project, job),
+                Some(JobScope::Success),
+                "Job needing itself wasn't played"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn manual_actions_recorded() {
+        let gitlab = gitlab().await;
+        let path =
+            std::env::temp_dir().join(format!("gitlabjobber-manual-{}.jsonl", std::process::id()));
+        let config = Config {
+            state_file: Some(path.display().to_string()),
+            ..config(&gitlab)
+        };
+
+        commands::act(&config, 30, 300, Decision::Cancel).await;
+
+        let events = StateStore::history(&path, 300)
+            .unwrap()
+            .into_iter()
+            .map(|record| record.event.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(events, ["canceled"], "Manual cancel wasn't recorded");
+        assert!(
+            StateStore::open(&path).unwrap().is_handled(300),
+            "Manually canceled job isn't handled"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn state_store() {
+        let gitlab = gitlab().await;
+        let path =
+            std::env::temp_dir().join(format!("gitlabjobber-state-{}.jsonl", std::process::id()));
+        // Job 300 canceled by a previous run
+        let previous = StateStore::open(&path).unwrap();
+        previous.record(
+            &JobInfo {
+                id: Some(300),
+                ..Default::default()
+            },
+            Event::Canceled { error: None },
+        );
+
+        let executor = Arc::new(Executor::new(
+            &Config {
+                state_file: Some(path.display().to_string()),
+                max_wait_time: Some(0),
+                ..config(&gitlab)
+            },
+            None,
+        ));
+        executor.run_once().await;
+
+        assert_eq!(
+            gitlab.job_status(30, 300),
+            Some(JobScope::Manual),
+            "Job handled by a previous run was canceled again"
+        );
+        let events = |job: u64| {
+            StateStore::history(&path, job)
+                .unwrap()
+                .into_iter()
+                .map(|record| record.event.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            events(200),
+            [
+                "decided cancel: duplicated",
+                "canceled",
+                "finished with status canceled"
+            ],
+            "Canceled job history is incomplete"
+        );
+        assert_eq!(events(300), ["canceled"], "Skipped job history changed");
+        assert_eq!(
+            events(201),
+            ["decided play", "played", "finished with status success"],
+            "Played job history is incomplete"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn repeated_decisions() {
+        init();
+        let gitlab = MockGitlab::start().await;
+        gitlab.add_project(90, "service");
+        gitlab.add_job(
+            90,
+            MockJob {
+                git_ref: "v1.0.0".to_owned(),
+                ..MockJob::manual(900, 9000)
+            },
+        );
+        // Tags can't be read, so the job is deferred by every scan
+        gitlab.fail(
+            Method::GET,
+            "/api/v4/projects/90/repository/tags",
+            StatusCode::INTERNAL_SERVER_ERROR,
+            10,
+        );
+        let path = std::env::temp_dir().join(format!(
+            "gitlabjobber-repeated-{}.jsonl",
+            std::process::id()
+        ));
+        let audit_path = path.with_extension("audit.jsonl");
+        let config = Config {
+            project_ids: Some(vec![90]),
+            state_file: Some(path.display().to_string()),
+            audit: Some(AuditConfig {
+                path: Some(audit_path.display().to_string()),
+                ..Default::default()
+            }),
+            retry: Some(configloader::RetryConfig {
+                attempts: Some(1),
+                ..Default::default()
+            }),
+            ..gitlab.config()
+        };
+
+        let executor = Arc::new(Executor::new(&config, None));
+        executor.run_once().await;
+        executor.run_once().await;
+        Arc::new(Executor::new(&config, None)).run_once().await;
+
+        let events = StateStore::history(&path, 900)
+            .unwrap()
+            .into_iter()
+            .map(|record| record.event.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(events, ["decided defer"], "Repeated decision was recorded");
+        let audited = std::fs::read_to_string(&audit_path)
+            .unwrap()
+            .matches("\"event\":\"decided\"")
+            .count();
+        assert_eq!(audited, 1, "Repeated decision was audited");
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&audit_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn resume_monitoring() {
+        init();
+        let gitlab = MockGitlab::start().await;
+        gitlab.add_project(60, "api");
+        for (job, status) in [
+            (600, JobScope::Success),
+            (601, JobScope::Running),
+            (602, JobScope::Failed),
+        ] {
+            gitlab.add_job(
+                60,
+                MockJob {
+                    status,
+                    ..MockJob::manual(job, job * 10)
+                },
+            );
+        }
+
+        // Played by a killed run, job 602 being seen over before
+        let path =
+            std::env::temp_dir().join(format!("gitlabjobber-resume-{}.jsonl", std::process::id()));
+        let previous = StateStore::open(&path).unwrap();
+        let now = Utc::now();
+        for (id, deadline) in [
+            (600, now + chrono::Duration::hours(1)),
+            (601, now - chrono::Duration::hours(1)),
+            (602, now + chrono::Duration::hours(1)),
+        ] {
+            previous.record(
+                &JobInfo {
+                    id: Some(id),
+                    proj_id: Some(60),
+                    ..Default::default()
+                },
+                Event::Played {
+                    error: None,
+                    deadline: Some(deadline),
+                },
+            );
+        }
+        previous.record(
+            &JobInfo {
+                id: Some(602),
+                proj_id: Some(60),
+                ..Default::default()
+            },
+            Event::Finished {
+                status: JobScope::Failed.to_string(),
+            },
+        );
+
+        let executor = Arc::new(Executor::new(
+            &Config {
+                project_ids: Some(vec![60]),
+                state_file: Some(path.display().to_string()),
+                ..gitlab.config()
+            },
+            None,
+        ));
+        executor.run_once().await;
+
+        let last_event = |job: u64| {
+            StateStore::history(&path, job)
+                .unwrap()
+                .pop()
+                .map(|record| record.event.to_string())
+        };
+        assert_eq!(
+            last_event(600).as_deref(),
+            Some("finished with status success"),
+            "Job finished while stopped wasn't reported"
+        );
+        assert_eq!(
+            last_event(601).as_deref(),
+            Some("max wait time elapsed"),
+            "Job past its deadline wasn't reported"
+        );
+        assert!(
+            !gitlab
+                .requests()
+                .contains(&"GET /api/v4/projects/60/jobs/602".to_owned()),
+            "Finished job was watched again"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn audit_log() {
+        let gitlab = gitlab().await;
+        let path =
+            std::env::temp_dir().join(format!("gitlabjobber-audit-{}.jsonl", std::process::id()));
+        let executor = Arc::new(Executor::new(
+            &Config {
+                audit: Some(AuditConfig {
+                    path: Some(path.display().to_string()),
+                    ..Default::default()
+                }),
+                max_wait_time: Some(0),
+                ..config(&gitlab)
+            },
+            None,
+        ));
+        executor.run_once().await;
+
+        let lines = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        let events = |job: u64| {
+            lines
+                .iter()
+                .filter(|line| line["job_id"] == job)
+                .map(|line| line["event"].as_str().unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            events(201),
+            [
+                "scanned",
+                "decided",
+                "play_requested",
+                "play_answered",
+                "finished"
+            ],
+            "Played job events are missing"
+        );
+        assert_eq!(
+            events(300),
+            [
+                "scanned",
+                "decided",
+                "cancel_requested",
+                "cancel_answered",
+                "finished"
+            ],
+            "Canceled job events are missing"
+        );
+        assert_eq!(
+            events(400),
+            [
+                "scanned",
+                "decided",
+                "cancel_requested",
+                "cancel_answered",
+                "finished"
+            ],
+            "Job without tag events are missing"
+        );
+
+        let line = |job: u64, event: &str| {
+            lines
+                .iter()
+                .find(|line| line["job_id"].as_u64() == Some(job) && line["event"] == event)
+                .unwrap()
+        };
+        let answer = line(201, "play_answered");
+        assert_eq!(
+            (
+                answer["succeeded"].as_bool(),
+                answer["http_status"].as_u64()
+            ),
+            (Some(true), Some(200)),
+            "Play answer isn't audited"
+        );
+        assert_eq!(answer["git_tag"], "v1.2.0", "Git tag isn't audited");
+        assert_eq!(
+            answer["project_id"].as_u64(),
+            Some(20),
+            "Project isn't audited"
+        );
+        assert_eq!(
+            answer["pipeline_id"].as_u64(),
+            Some(2001),
+            "Pipeline isn't audited"
+        );
+        assert!(
+            answer.get("user_mail").is_some() && answer.get("timestamp").is_some(),
+            "User mail or timestamp isn't audited"
+        );
+        let decision = line(300, "decided");
+        assert_eq!(decision["decision"], "cancel", "Decision isn't audited");
+        assert_eq!(decision["reason"], "invalid_tag", "Reason isn't audited");
+        assert_eq!(
+            line(200, "cancel_answered")["http_status"].as_u64(),
+            Some(201),
+            "Cancel answer status isn't the one Gitlab answered"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn audit_decision_details() {
+        let audited = |reason: MailReason| {
+            serde_json::to_value(AuditEvent::Decided {
+                decision: Decision::Cancel,
+                reason: Some(reason.name().to_owned()),
+                details: reason.details(),
+            })
+            .unwrap()
+        };
+
+        assert_eq!(
+            audited(MailReason::TagRule(TagViolation::Prerelease))["details"],
+            TagViolation::Prerelease.to_string(),
+            "Broken tag rule isn't audited"
+        );
+        assert_eq!(
+            audited(MailReason::PrerequisiteFailed("deploy-db".to_owned()))["details"],
+            "deploy-db",
+            "Failed prerequisite isn't audited"
+        );
+        assert!(
+            audited(MailReason::Duplicated).get("details").is_none(),
+            "Reason without details got some"
+        );
+    }
+
+    #[test]
+    fn audit_rotation() {
+        let path = std::env::temp_dir().join(format!(
+            "gitlabjobber-rotation-{}.jsonl",
+            std::process::id()
+        ));
+        let audit = AuditLog::new(&AuditConfig {
+            path: Some(path.display().to_string()),
+            max_size: Some(200),
+            keep: Some(2),
+        })
+        .unwrap();
+        let job = JobInfo {
+            id: Some(1),
+            ..Default::default()
+        };
+        for event in core::iter::repeat_n(AuditEvent::Scanned, 10) {
+            audit.write(&job, event);
+        }
+
+        let rotated = |number: u32| {
+            let mut name = path.clone().into_os_string();
+            name.push(format!(".{number}"));
+            std::path::PathBuf::from(name)
+        };
+        assert!(
+            std::fs::metadata(&path).unwrap().len() <= 200,
+            "Audit log wasn't rotated"
+        );
+        assert!(
+            rotated(1).exists() && rotated(2).exists(),
+            "Rotated files weren't kept"
+        );
+        assert!(!rotated(3).exists(), "Too many rotated files were kept");
+
+        for file in [path.clone(), rotated(1), rotated(2)] {
+            std::fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn metrics_textfile() {
+        let gitlab = gitlab().await;
+        let path =
+            std::env::temp_dir().join(format!("gitlabjobber-metrics-{}.prom", std::process::id()));
+        let executor = Arc::new(Executor::new(
+            &Config {
+                metrics: Some(MetricsConfig {
+                    textfile: Some(path.display().to_string()),
+                    ..Default::default()
+                }),
+                max_wait_time: Some(0),
+                ..config(&gitlab)
+            },
+            None,
+        ));
+        executor.run_once().await;
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        for line in [
+            "# TYPE gitlabjobber_jobs_discovered gauge",
+            "gitlabjobber_jobs_discovered{project=\"20\"} 2",
+            "gitlabjobber_decisions_total{decision=\"play\",reason=\"none\"} 1",
+            "gitlabjobber_decisions_total{decision=\"cancel\",reason=\"duplicated\"} 1",
+            "gitlabjobber_decisions_total{decision=\"cancel\",reason=\"invalid_tag\"} 2",
+            "gitlabjobber_jobs_finished_total{status=\"success\"} 1",
+            "gitlabjobber_jobs_finished_total{status=\"canceled\"} 3",
+            "gitlabjobber_job_wait_seconds_count{outcome=\"success\"} 1",
+            "# TYPE gitlabjobber_gitlab_request_duration_seconds histogram",
+            "gitlabjobber_gitlab_request_duration_seconds_count{method=\"POST\",endpoint=\"/api/v4/projects/:id/jobs/:id/play\",status=\"200\"} 1",
+        ] {
+            assert!(text.lines().any(|found| found == line), "Missing {line} in:\n{text}");
+        }
+        assert!(
+            !text.contains("gitlabjobber_action_failures_total"),
+            "No action failed"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn metrics_endpoint() {
+        use std::io::{Read as _, Write as _};
+
+        let metrics = Arc::new(Metrics::default());
+        metrics.discovered([(20, 3), (30, 1)]);
+        metrics.discovered([(20, 2)]);
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        tokio::spawn(Arc::clone(&metrics).serve(addr));
+
+        let get = |path: &'static str| {
+            tokio::task::spawn_blocking(move || {
+                let mut stream = core::iter::repeat_n(addr, 50)
+                    .find_map(|addr| {
+                        std::net::TcpStream::connect(addr)
+                            .inspect_err(|_| {
+                                std::thread::sleep(core::time::Duration::from_millis(20));
+                            })
+                            .ok()
+                    })
+                    .unwrap();
+                write!(
+                    stream,
+                    "GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n"
+                )
+                .unwrap();
+                let mut response = String::new();
+                stream.read_to_string(&mut response).unwrap();
+                response
+            })
+        };
+
+        let response = get("/metrics").await.unwrap();
+        assert!(
+            response.starts_with("HTTP/1.1 200"),
+            "Metrics weren't served: {response}"
+        );
+        assert!(
+            response.contains("gitlabjobber_jobs_discovered{project=\"20\"} 2")
+                && response.contains("gitlabjobber_jobs_discovered{project=\"30\"} 0"),
+            "Served jobs aren't the ones of the latest scan: {response}"
+        );
+        assert!(
+            get("/").await.unwrap().starts_with("HTTP/1.1 404"),
+            "Other paths must not be found"
+        );
+    }
+
+    #[test]
+    fn json_log_lines() {
+        let job = JobInfo {
+            id: Some(201),
+            proj_id: Some(20),
+            pipeline_id: Some(2001),
+            git_tag: Some("v1.2.0".to_owned()),
+            status: Some(JobScope::Success),
+            ..Default::default()
+        };
+        let context = JobContext::from(&job).decision(Decision::Play);
+        let fields = context.fields();
+        let line = logging::json_line(
+            &log::Record::builder()
+                .level(log::Level::Info)
+                .target("gitlabjobber::executor")
+                .args(format_args!("Job {} finished", job.id.unwrap_or_default()))
+                .key_values(&fields)
+                .build(),
+        );
+
+        let parsed: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed["message"], "Job 201 finished", "Message is missing");
+        assert_eq!(parsed["level"], "INFO", "Level is missing");
+        assert_eq!(
+            parsed["job_id"].as_u64(),
+            Some(201),
+            "Job id isn't a number"
+        );
+        assert_eq!(
+            parsed["project_id"].as_u64(),
+            Some(20),
+            "Project is missing"
+        );
+        assert_eq!(
+            parsed["pipeline_id"].as_u64(),
+            Some(2001),
+            "Pipeline is missing"
+        );
+        assert_eq!(parsed["git_tag"], "v1.2.0", "Git tag is missing");
+        assert_eq!(parsed["decision"], "play", "Decision is missing");
+        assert_eq!(parsed["status"], "success", "Status is missing");
+        assert!(parsed.get("timestamp").is_some(), "Timestamp is missing");
+
+        let untagged = JobInfo {
+            id: Some(300),
+            ..Default::default()
+        };
+        assert_eq!(
+            JobContext::from(&untagged).fields().len(),
+            1,
+            "Only known fields are logged"
+        );
+    }
+
+    #[test]
+    fn digest_reports() {
+        let job = |id: u64, user: &str, status: JobScope| JobInfo {
+            id: Some(id),
+            proj_name: Some("backend".to_owned()),
+            user_mail: Some(user.to_owned()),
+            status: Some(status),
+            ..Default::default()
+        };
+        let entries = vec![
+            (
+                job(1, "dev@test.tst", JobScope::Success),
+                MailReason::Status(JobScope::Success),
+            ),
+            (
+                job(2, "dev@test.tst", JobScope::Canceled),
+                MailReason::Duplicated,
+            ),
+            (
+                job(3, "other@test.tst", JobScope::Failed),
+                MailReason::Status(JobScope::Failed),
+            ),
+            (
+                job(4, "other@test.tst", JobScope::Running),
+                MailReason::MaxWaitElapsed,
+            ),
+            (
+                job(5, "other@test.tst", JobScope::Manual),
+                MailReason::OutsideDeployWindow,
+            ),
+        ];
+        let smtp = SmtpConfig {
+            from: Some("jobber@test.tst".to_owned()),
+            to: Some("ops@test.tst".to_owned()),
+            ..Default::default()
+        };
+
+        let recipients = utils::digest_messages(&entries, &smtp)
+            .iter()
+            .map(|message| {
+                message
+                    .headers()
+                    .get_raw("To")
+                    .unwrap_or_default()
+                    .to_owned()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            recipients,
+            ["ops@test.tst", "dev@test.tst", "other@test.tst"],
+            "Ops and each user must get a report"
+        );
+
+        let all = entries.iter().collect::<Vec<_>>();
+        let html = utils::report_html(&all);
+        for section in [
+            "Played and succeeded (1)",
+            "Failed (1)",
+            "Canceled (1)",
+            "Timed out (1)",
+        ] {
+            assert!(html.contains(section), "Missing section {section}");
+        }
+        assert!(
+            html.contains("canceled due to duplicated pipeline"),
+            "Cancel reason is missing"
+        );
+        assert!(!html.contains(">5<"), "Deferred jobs aren't reported");
+        let text = utils::report_text(&all);
+        assert!(
+            text.contains("Canceled (1)") && text.contains("canceled due to duplicated pipeline"),
+            "Plain text report is missing the canceled jobs"
+        );
+        assert!(!text.contains('<'), "Plain text report holds tags");
+
+        let dev = entries
+            .iter()
+            .filter(|entry| entry.0.user_mail.as_deref() == Some("dev@test.tst"))
+            .collect::<Vec<_>>();
+        assert!(
+            !utils::report_html(&dev).contains("Timed out"),
+            "Users only get their jobs"
+        );
+        assert!(
+            utils::digest_messages(&[], &smtp).is_empty(),
+            "Empty runs aren't reported"
+        );
+
+        let hostile = (
+            JobInfo {
+                name: Some("<b>deploy</b>".to_owned()),
+                url: Some("http://gitlab/\" onclick=\"x".to_owned()),
+                ..job(6, "dev@test.tst", JobScope::Success)
+            },
+            MailReason::Status(JobScope::Success),
+        );
+        let escaped = utils::report_html(&[&hostile]);
+        assert!(
+            escaped.contains("&lt;b&gt;deploy&lt;&#x2f;b&gt;") && !escaped.contains("<b>"),
+            "Job values aren't escaped: {escaped}"
+        );
+        assert!(
+            escaped.contains("href=\"http:&#x2f;&#x2f;gitlab&#x2f;&quot; onclick=&quot;x\""),
+            "Job url isn't quoted and escaped: {escaped}"
+        );
+    }
+
+    #[test]
+    fn template_reasons() {
+        let reasons = [
+            MailReason::Duplicated,
+            MailReason::InvalidTag,
+            MailReason::TagRule(TagViolation::Prerelease),
+            MailReason::UnprotectedTag,
+            MailReason::OutsideDeployWindow,
+            MailReason::ErrorToCancel,
+            MailReason::ErrorToPlay,
+            MailReason::MaxWaitElapsed,
+            MailReason::PreviousFailed(1),
+            MailReason::PrerequisiteFailed("deploy-db".to_owned()),
+            MailReason::Status(JobScope::Success),
+        ];
+        // A new reason breaks this match, to be added to the list above
+        for reason in &reasons {
+            match *reason {
+                MailReason::Duplicated
+                | MailReason::InvalidTag
+                | MailReason::TagRule(_)
+                | MailReason::UnprotectedTag
+                | MailReason::OutsideDeployWindow
+                | MailReason::ErrorToCancel
+                | MailReason::ErrorToPlay
+                | MailReason::MaxWaitElapsed
+                | MailReason::PreviousFailed(_)
+                | MailReason::PrerequisiteFailed(_)
+                | MailReason::Status(_) => {}
+            }
+        }
+
+        assert_eq!(
+            reasons.iter().map(MailReason::name).collect::<Vec<_>>(),
+            templates::REASONS,
+            "Templates can't be set for every mail reason"
+        );
+    }
+
+    #[test]
+    fn mail_templates() {
+        let dir =
+            std::env::temp_dir().join(format!("gitlabjobber-templates-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = |name: &str, source: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, source).unwrap();
+            Some(path.display().to_string())
+        };
+        let duplicated = MailTemplate {
+            subject: file("subject", "[{{ proj_name }}] {{ summary }}\n"),
+            html: file(
+                "html",
+                "<p>{{ name }} on {{ git_tag | default('no tag') }}</p>",
+            ),
+            ..Default::default()
+        };
+        let broken = MailTemplate {
+            subject: file("broken", "{{ name "),
+            ..Default::default()
+        };
+        let smtp = SmtpConfig {
+            templates: Some(HashMap::from([
+                ("duplicated".to_owned(), duplicated),
+                ("status".to_owned(), broken),
+                ("unknown".to_owned(), MailTemplate::default()),
+            ])),
+            ..Default::default()
+        };
+
+        let problems = MailTemplates::check(&smtp);
+        assert_eq!(
+            problems.len(),
+            2,
+            "Problems aren't all reported: {problems:?}"
+        );
+        assert!(
+            problems[0].starts_with("smtp subject template") && problems[0].contains("of status"),
+            "Syntax error isn't reported"
+        );
+        assert!(
+            problems[1].contains("unknown mail reason \"unknown\""),
+            "Unknown reason isn't reported"
+        );
+
+        let job = JobInfo {
+            id: Some(7),
+            name: Some("<deploy>".to_owned()),
+            proj_name: Some("backend".to_owned()),
+            ..Default::default()
+        };
+        let templates = MailTemplates::new(&smtp);
+        assert_eq!(
+            templates.subject(&job, &MailReason::Duplicated).as_deref(),
+            Some("[backend] Job 7 from project backend canceled due to duplicated pipeline"),
+            "Subject isn't rendered from its template"
+        );
+        assert_eq!(
+            templates.html(&job, &MailReason::Duplicated).as_deref(),
+            Some("<p>&lt;deploy&gt; on no tag</p>"),
+            "Html body isn't rendered escaped"
+        );
+        assert_eq!(
+            templates.text(&job, &MailReason::Duplicated),
+            None,
+            "Missing templates must fall back"
+        );
+        assert_eq!(
+            templates.subject(&job, &MailReason::Status(JobScope::Success)),
+            None,
+            "Broken templates must fall back"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn scan_targets() {
+        let gitlab = gitlab().await;
+        for (group, project, job) in [(Some(2), 60, 600), (None, 70, 700)] {
+            gitlab.add_project(project, "service");
+            gitlab.add_tags(project, &["v1.0.0"]);
+            gitlab.add_job(
+                project,
+                MockJob {
+                    git_ref: "v1.0.0".to_owned(),
+                    ..MockJob::manual(job, job)
+                },
+            );
+            if let Some(group) = group {
+                gitlab.add_group(group, &[project]);
+            }
+        }
+
+        let executor = Arc::new(Executor::new(
+            &Config {
+                group_id: None,
+                group_ids: Some(vec![1, 2]),
+                project_ids: Some(vec![70, 30]),
+                exclude_projects: Some(vec![30]),
+                ..config(&gitlab)
+            },
+            None,
+        ));
+        executor.run_once().await;
+
+        for (project, job) in [(20, 201), (60, 600), (70, 700)] {
+            assert_eq!(
+                gitlab.job_status(project, job),
+                Some(JobScope::Success),
+                "Job {job} of a scanned project wasn't played"
+            );
+        }
+        assert_eq!(
+            gitlab.job_status(30, 300),
+            Some(JobScope::Manual),
+            "Job of an excluded project was acted on"
+        );
+        assert!(
+            !gitlab
+                .requests()
+                .iter()
+                .any(|request| request.starts_with("GET /api/v4/projects/30/")),
+            "Excluded project was scanned"
+        );
+    }
+
+    #[tokio::test]
+    async fn job_filters() {
+        let gitlab = gitlab().await;
+        // Newest pipeline, but not a managed job
+        gitlab.add_job(
+            20,
+            MockJob {
+                name: "cleanup-cache".to_owned(),
+                git_ref: "v1.2.0".to_owned(),
+                ..MockJob::manual(202, 2002)
+            },
+        );
+
+        let pattern = |source: &str| JobPattern::try_from(source.to_owned()).unwrap();
+        let production = JobFilter {
+            environment: Some(PatternFilter {
+                include: Some(vec![pattern("production")]),
+                exclude: None,
+            }),
+            ..Default::default()
+        };
+        let config = Config {
+            jobs: Some(JobFilter {
+                name: Some(PatternFilter {
+                    include: None,
+                    exclude: Some(vec![pattern("cleanup*")]),
+                }),
+                ..Default::default()
+            }),
+            projects: Some(HashMap::from([(
+                "30".to_owned(),
+                ProjectConfig {
+                    jobs: Some(production),
+                    ..Default::default()
+                },
+            )])),
+            ..config(&gitlab)
+        };
+        let executor = Arc::new(Executor::new(&config, None));
+        executor.run_once().await;
+
+        for (project, job, status) in [
+            (20, 200, JobScope::Canceled),
+            (20, 201, JobScope::Success),
+            (20, 202, JobScope::Manual),
+            (30, 300, JobScope::Manual),
+        ] {
+            assert_eq!(
+                gitlab.job_status(project, job),
+                Some(status),
+                "Wrong status for job {job}"
+            );
+        }
+    }
+
+    #[test]
+    fn plan_table() {
+        let played = JobInfo {
+            id: Some(11),
+            proj_name: Some("backend".to_owned()),
+            pipeline_id: Some(7),
+            git_tag: Some("v1.2.0".to_owned()),
+            ..Default::default()
+        };
+        let canceled = JobInfo {
+            id: Some(10),
+            proj_name: Some("backend".to_owned()),
+            pipeline_id: Some(6),
+            ..Default::default()
+        };
+        let dependent = JobInfo {
+            id: Some(12),
+            proj_name: Some("frontend".to_owned()),
+            pipeline_id: Some(8),
+            ..Default::default()
+        };
+        let verified_jobs = HashMap::from([
+            (&played, (Decision::Play, None)),
+            (&canceled, (Decision::Cancel, Some(MailReason::Duplicated))),
+            (
+                &dependent,
+                (
+                    Decision::Cancel,
+                    Some(MailReason::PrerequisiteFailed("deploy-db".to_owned())),
+                ),
+            ),
+        ]);
+
+        let table = utils::plan_table(&verified_jobs);
+        let lines = table.lines().collect::<Vec<_>>();
+
+        assert_eq!(
+            lines,
+            [
+                "JOB  PROJECT   PIPELINE  TAG     DECISION  REASON",
+                "10   backend   6         -       cancel    duplicated",
+                "11   backend   7         v1.2.0  play      -",
+                "12   frontend  8         -       cancel    prerequisite_failed: deploy-db",
+            ],
+            "Unexpected plan table:\n{table}"
+        );
+    }
+
+    #[test]
+    fn cli_overrides() {
+        use clap::Parser as _;
+
+        let mut config = Config {
+            group_id: Some(1),
+            max_wait_time: Some(1800),
+            ..Default::default()
+        };
+
+        let cli =
+            cli::Cli::try_parse_from(["gitlabjobber", "list", "--project-id", "123"]).unwrap();
+        assert_eq!(cli.command, Some(cli::Command::List), "Wrong subcommand");
+        cli.overrides.apply(&mut config);
+        assert_eq!(
+            config.group_id, None,
+            "A given project must replace the group"
+        );
+        assert_eq!(config.project_id, Some(123), "Project wasn't overridden");
+        assert_eq!(
+            config.max_wait_time,
+            Some(1800),
+            "Unset flags must keep config"
+        );
+
+        let run = cli::Cli::try_parse_from(["gitlabjobber", "--max-wait-time", "60", "--dry-run"])
+            .unwrap();
+        assert_eq!(run.command, None, "Run is the default subcommand");
+        run.overrides.apply(&mut config);
+        assert_eq!(
+            config.max_wait_time,
+            Some(60),
+            "Max wait time wasn't overridden"
+        );
+        assert_eq!(config.dry_run, Some(true), "Dry run wasn't overridden");
+    }
+
+    #[test]
+    fn config_check_load_errors() {
+        let path =
+            std::env::temp_dir().join(format!("gitlabjobber-config-{}.env", std::process::id()));
+        std::fs::write(&path, "group_id=1\nprivate_token=\"token\"\n").unwrap();
+
+        let loaded = Config::load_from_vars([("ENV_FILE".to_owned(), path.display().to_string())]);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            commands::config_problems(loaded.as_ref().map_err(|error| *error)),
+            ["There's no gitlab server to scan"],
+            "Missing base_url must reach the checker"
+        );
     }
 
     #[tokio::test]
     #[ignore = "send email"]
+    #[expect(
+        clippy::redundant_test_prefix,
+        clippy::str_to_string,
+        clippy::uninlined_format_args,
+        clippy::unnecessary_semicolon,
+        reason = "Kept as written before the lint rules"
+    )]
     async fn test_email() {
         init();
 
@@ -104,8 +1681,9 @@ mod integration_tests {
 
         let message = utils::mail_message(
             &test_job,
-            MailReason::ErrorToPlay,
-            &config.unwrap_or_default(),
+            &MailReason::ErrorToPlay,
+            &config.clone().unwrap_or_default(),
+            &MailTemplates::new(&config.unwrap_or_default()),
         );
 
         let mail_relay = mail_relay_handle.await.unwrap_or_default();
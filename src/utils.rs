@@ -1,13 +1,20 @@
-use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::collections::{HashMap, HashSet};
 
 use gitlabapi::prelude::*;
 use mailsender::prelude::*;
 
-use crate::MailReason;
-use crate::SmtpConfig;
-use log::{error, warn};
+use crate::logging::JobContext;
+use crate::state::Record;
+use crate::templates::MailTemplates;
+use crate::{Config, DedupKeep, DedupKey, DeployGraph, SmtpConfig, TagRules};
+use crate::{Decision, MailReason};
+use chrono::{DateTime, Utc};
+use configloader::JobPattern;
+use log::error;
+use minijinja::HtmlEscape;
+use semver::Version;
 
-/// Build the mail relay
+/// Build the mail relay.
 pub async fn mailrelay_build(smtp_config: SmtpConfig) -> Option<SmtpTransport> {
     if smtp_config.is_valid() {
         match MailSender::try_new(smtp_config.clone()).await {
@@ -16,7 +23,7 @@ pub async fn mailrelay_build(smtp_config: SmtpConfig) -> Option<SmtpTransport> {
                 mailer.relay
             }
             Err(error) => {
-                error!("{}", error);
+                error!("{error}");
                 None
             }
         }
@@ -25,100 +32,659 @@ pub async fn mailrelay_build(smtp_config: SmtpConfig) -> Option<SmtpTransport> {
     }
 }
 
-/// Build mail message facilitator
-pub fn mail_message(job: &JobInfo, reason: &MailReason, builder: &SmtpConfig) -> Message {
-    let subject = match *reason {
+/// Build mail message facilitator, from the user templates when there's some.
+pub fn mail_message(
+    job: &JobInfo,
+    reason: &MailReason,
+    builder: &SmtpConfig,
+    templates: &MailTemplates,
+) -> Message {
+    let to = job.user_mail.clone();
+
+    debug!("Sending mail to {:?}", &to);
+
+    builder.body_builder(
+        templates
+            .subject(job, reason)
+            .unwrap_or_else(|| mail_subject(job, reason)),
+        templates.html(job, reason).unwrap_or_else(|| job.to_html()),
+        templates.text(job, reason).unwrap_or_else(|| job.to_text()),
+        to,
+    )
+}
+
+/// What happened to a job, as told in the built-in mail subjects.
+pub fn mail_subject(job: &JobInfo, reason: &MailReason) -> String {
+    match reason.clone() {
         MailReason::Duplicated => {
             format!("Job {job} canceled due to duplicated pipeline")
         }
         MailReason::InvalidTag => format!("Job {job} canceled due to invalid git tag"),
+        MailReason::TagRule(violation) => format!(
+            "Job {job} canceled because git tag {} {violation}",
+            job.git_tag.as_deref().unwrap_or("unknown")
+        ),
+        MailReason::UnprotectedTag => job.git_tag.as_deref().map_or_else(
+            || format!("Job {job} canceled because it has no git tag to check protection"),
+            |tag| format!("Job {job} canceled because git tag {tag} isn't protected"),
+        ),
+        MailReason::OutsideDeployWindow => format!("Job {job} deferred until next deploy window"),
         MailReason::ErrorToCancel => format!("Error trying to cancel job {job}"),
         MailReason::ErrorToPlay => format!("Error to start job {job}"),
         MailReason::MaxWaitElapsed => format!("Max wait time elapsed for job {job}"),
+        MailReason::PreviousFailed(previous) => {
+            format!("Job {job} left manual because job {previous} played before it failed")
+        }
+        MailReason::PrerequisiteFailed(prerequisite) => {
+            format!("Job {job} canceled because its prerequisite job {prerequisite} didn't succeed")
+        }
         MailReason::Status(status) => format!("Status of job {job}: {status}"),
+    }
+}
+
+/// Part of a run report a job is listed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReportSection {
+    Succeeded,
+    Failed,
+    Canceled,
+    Skipped,
+    TimedOut,
+}
+
+impl ReportSection {
+    const ALL: [Self; 5] = [
+        Self::Succeeded,
+        Self::Failed,
+        Self::Canceled,
+        Self::Skipped,
+        Self::TimedOut,
+    ];
+
+    const fn title(self) -> &'static str {
+        match self {
+            Self::Succeeded => "Played and succeeded",
+            Self::Failed => "Failed",
+            Self::Canceled => "Canceled",
+            Self::Skipped => "Left manual",
+            Self::TimedOut => "Timed out",
+        }
+    }
+
+    /// Whether the jobs of the section are listed with their reason rather than their status.
+    const fn shows_reason(self) -> bool {
+        matches!(self, Self::Canceled | Self::Skipped)
+    }
+
+    /// Section of a mail reason, `None` for deferred jobs.
+    const fn of(reason: &MailReason) -> Option<Self> {
+        match *reason {
+            MailReason::Status(JobScope::Success) => Some(Self::Succeeded),
+            MailReason::Status(JobScope::Canceled)
+            | MailReason::Duplicated
+            | MailReason::InvalidTag
+            | MailReason::TagRule(_)
+            | MailReason::UnprotectedTag
+            | MailReason::PrerequisiteFailed(_) => Some(Self::Canceled),
+            MailReason::PreviousFailed(_) => Some(Self::Skipped),
+            MailReason::Status(_) | MailReason::ErrorToCancel | MailReason::ErrorToPlay => {
+                Some(Self::Failed)
+            }
+            MailReason::MaxWaitElapsed => Some(Self::TimedOut),
+            MailReason::OutsideDeployWindow => None,
+        }
+    }
+}
+
+/// Jobs of each non empty report section.
+fn report_sections<'entry>(
+    entries: &[&'entry (JobInfo, MailReason)],
+) -> Vec<(ReportSection, Vec<&'entry (JobInfo, MailReason)>)> {
+    ReportSection::ALL
+        .into_iter()
+        .map(|section| {
+            let jobs = entries
+                .iter()
+                .copied()
+                .filter(|entry| ReportSection::of(&entry.1) == Some(section))
+                .collect::<Vec<_>>();
+            (section, jobs)
+        })
+        .filter(|report| !report.1.is_empty())
+        .collect()
+}
+
+/// Header of a report section table.
+fn report_header(section: ReportSection) -> Vec<String> {
+    let details = if section.shows_reason() {
+        "Reason"
+    } else {
+        "Status"
     };
+    ["Job", "Project", "Name", "Git tag", "User", details]
+        .map(str::to_owned)
+        .to_vec()
+}
 
-    let to = job.user_mail.clone();
+/// Cells of a job in a report section, its reason when canceled or left manual, or else its
+/// status.
+fn report_row(section: ReportSection, job: &JobInfo, reason: &MailReason) -> Vec<String> {
+    let cell = |value: Option<&str>| value.unwrap_or("unknown").to_owned();
+    let details = if section.shows_reason() {
+        mail_subject(job, reason)
+    } else {
+        job.status.unwrap_or(JobScope::Invalid).to_string()
+    };
+    vec![
+        job.id.unwrap_or_default().to_string(),
+        cell(job.proj_name.as_deref()),
+        cell(job.name.as_deref()),
+        cell(job.git_tag.as_deref()),
+        cell(job.user_mail.as_deref()),
+        details,
+    ]
+}
 
-    debug!("Sending mail to {:?}", &to);
+/// HTML report of the jobs of a run, by section, every value from Gitlab being escaped.
+pub fn report_html(entries: &[&(JobInfo, MailReason)]) -> String {
+    let sections = report_sections(entries)
+        .into_iter()
+        .map(|(section, jobs)| {
+            let header = report_header(section)
+                .iter()
+                .map(|cell| format!("<th>{cell}</th>"))
+                .collect::<Vec<_>>()
+                .concat();
+            let rows = jobs
+                .iter()
+                .map(|entry| {
+                    let mut cells = report_row(section, &entry.0, &entry.1).into_iter();
+                    let id = cells.next().unwrap_or_default();
+                    let cells = cells
+                        .map(|cell| format!("<td>{}</td>", HtmlEscape(&cell)))
+                        .collect::<Vec<_>>()
+                        .concat();
+                    format!(
+                        "<tr><td><a href=\"{}\">{id}</a></td>{cells}</tr>",
+                        HtmlEscape(entry.0.url.as_deref().unwrap_or_default())
+                    )
+                })
+                .collect::<Vec<_>>();
+            format!(
+                "<h2>{} ({})</h2>\n<table>\n<tr>{header}</tr>\n{}\n</table>",
+                section.title(),
+                jobs.len(),
+                rows.join("\n")
+            )
+        })
+        .collect::<Vec<_>>();
 
-    builder.body_builder(subject, job.to_html(), to)
-}
-
-/// Reorder got jobs by Project id and Pipeline id skipping the first pipeline
-pub fn pipelines_tocancel(
-    jobs: &HashMap<ProjectID, HashSet<JobInfo>>,
-) -> HashMap<ProjectID, Vec<PipelineID>> {
-    let mut pipelines_tocancel = HashMap::new();
-    jobs.iter()
-        .map(|(proj, jobs_)| {
-            (*proj, {
-                let mut temp = BinaryHeap::from(
-                    jobs_.iter()
-                        .map(|job| PipelineID(job.pipeline_id.unwrap()))
-                        .collect::<Vec<PipelineID>>(),
-                );
-                let higher = temp.peek().copied();
-                higher.map_or_else(
-                    || Vec::with_capacity(0),
-                    |higher_| {
-                        temp.drain()
-                            .filter(|id| id != &higher_)
-                            .collect::<Vec<PipelineID>>()
-                    },
+    format!(
+        "<div style=\"text-align: left;\">\n{}\n</div>",
+        sections.join("\n")
+    )
+}
+
+/// Plain text report of the jobs of a run, by section.
+pub fn report_text(entries: &[&(JobInfo, MailReason)]) -> String {
+    report_sections(entries)
+        .into_iter()
+        .map(|(section, jobs)| {
+            let rows = core::iter::once(report_header(section))
+                .chain(
+                    jobs.iter()
+                        .map(|entry| report_row(section, &entry.0, &entry.1)),
                 )
+                .collect::<Vec<_>>();
+            format!(
+                "{} ({})\n\n{}\n",
+                section.title(),
+                jobs.len(),
+                text_table(&rows)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Run reports: every job to the configured recipients, and their own jobs to each user.
+pub fn digest_messages(entries: &[(JobInfo, MailReason)], smtp: &SmtpConfig) -> Vec<Message> {
+    let listed = entries
+        .iter()
+        .filter(|entry| ReportSection::of(&entry.1).is_some())
+        .collect::<Vec<_>>();
+    if listed.is_empty() {
+        return vec![];
+    }
+
+    let mut reports = vec![];
+    if let Some(ops) = smtp.to.as_ref() {
+        reports.push((ops.clone(), listed.clone()));
+    }
+    let mut users: Vec<(String, Vec<&(JobInfo, MailReason)>)> = vec![];
+    for &entry in &listed {
+        let Some(user) = entry.0.user_mail.as_ref() else {
+            continue;
+        };
+        match users.iter_mut().find(|report| &report.0 == user) {
+            Some(report) => report.1.push(entry),
+            None => users.push((user.clone(), vec![entry])),
+        }
+    }
+    reports.extend(users);
+
+    reports
+        .into_iter()
+        .filter_map(|(recipients, jobs)| {
+            let subject = format!("Run report: {} jobs", jobs.len());
+            let message =
+                smtp.report_builder(subject, report_html(&jobs), report_text(&jobs), &recipients);
+            if message.is_none() {
+                error!("Couldn't build the run report to {recipients}");
+            }
+            message
+        })
+        .collect()
+}
+
+/// Align rows in columns, the first row being the header.
+fn text_table(rows: &[Vec<String>]) -> String {
+    let widths = rows.iter().fold(vec![], |widths: Vec<usize>, row| {
+        row.iter()
+            .enumerate()
+            .map(|(col, cell)| {
+                cell.chars()
+                    .count()
+                    .max(widths.get(col).copied().unwrap_or_default())
             })
+            .collect()
+    });
+
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_owned()
         })
-        .for_each(|(key, vec)| {
-            pipelines_tocancel.entry(key).or_insert(vec);
-        });
+        .collect::<Vec<_>>()
+        .join("\n")
+}
 
-    pipelines_tocancel
+fn or_unknown<T: ToString>(value: Option<&T>) -> String {
+    value.map_or_else(|| "-".to_owned(), ToString::to_string)
 }
 
-/// Check if the job must be canceled or played
+/// Render validation decisions as a text table, sorted by project and job.
+pub fn plan_table(verified_jobs: &HashMap<&JobInfo, (Decision, Option<MailReason>)>) -> String {
+    let mut jobs = verified_jobs.iter().collect::<Vec<_>>();
+    jobs.sort_by_key(|&(job, _)| (job.proj_name.clone(), job.id));
+
+    let header = ["JOB", "PROJECT", "PIPELINE", "TAG", "DECISION", "REASON"].map(str::to_owned);
+    let rows = core::iter::once(header.to_vec())
+        .chain(jobs.into_iter().map(|(job, context)| {
+            vec![
+                or_unknown(job.id.as_ref()),
+                or_unknown(job.proj_name.as_ref()),
+                or_unknown(job.pipeline_id.as_ref()),
+                or_unknown(job.git_tag.as_ref()),
+                context.0.to_string(),
+                or_unknown(
+                    context
+                        .1
+                        .as_ref()
+                        .map(|reason| {
+                            reason.details().map_or_else(
+                                || reason.name().to_owned(),
+                                |details| format!("{}: {details}", reason.name()),
+                            )
+                        })
+                        .as_ref(),
+                ),
+            ]
+        }))
+        .collect::<Vec<_>>();
+
+    text_table(&rows)
+}
+
+/// Render the state records of jobs as a text table, in their order.
+pub fn history_table(records: &[Record]) -> String {
+    let header = ["TIME", "JOB", "PROJECT", "PIPELINE", "EVENT"].map(str::to_owned);
+    let rows = core::iter::once(header.to_vec())
+        .chain(records.iter().map(|record| {
+            vec![
+                record.at.to_rfc3339(),
+                record.job_id.to_string(),
+                or_unknown(record.project_id.as_ref()),
+                or_unknown(record.pipeline_id.as_ref()),
+                record.event.to_string(),
+            ]
+        }))
+        .collect::<Vec<_>>();
+
+    text_table(&rows)
+}
+
+/// Render found jobs as a text table, sorted by project and job.
+pub fn jobs_table(proj_jobs: &HashMap<ProjectID, HashSet<JobInfo>>) -> String {
+    let mut jobs = proj_jobs.values().flatten().collect::<Vec<_>>();
+    jobs.sort_by_key(|job| (job.proj_name.clone(), job.id));
+
+    let header = ["JOB", "NAME", "PROJECT", "PIPELINE", "TAG", "USER", "URL"].map(str::to_owned);
+    let rows = core::iter::once(header.to_vec())
+        .chain(jobs.into_iter().map(|job| {
+            vec![
+                or_unknown(job.id.as_ref()),
+                or_unknown(job.name.as_ref()),
+                or_unknown(job.proj_name.as_ref()),
+                or_unknown(job.pipeline_id.as_ref()),
+                or_unknown(job.git_tag.as_ref()),
+                or_unknown(job.user_mail.as_ref()),
+                or_unknown(job.url.as_ref()),
+            ]
+        }))
+        .collect::<Vec<_>>();
+
+    text_table(&rows)
+}
+
+/// Render job informations, one per line.
+pub fn job_details(job: &JobInfo) -> String {
+    let rows = [
+        ("Job id:", or_unknown(job.id.as_ref())),
+        ("Job name:", or_unknown(job.name.as_ref())),
+        ("Stage:", or_unknown(job.stage.as_ref())),
+        ("Environment:", or_unknown(job.environment.as_ref())),
+        ("Job status:", or_unknown(job.status.as_ref())),
+        ("Project name:", or_unknown(job.proj_name.as_ref())),
+        ("Deploy project id:", or_unknown(job.proj_id.as_ref())),
+        ("Deploy pipeline id:", or_unknown(job.pipeline_id.as_ref())),
+        ("Source project id:", or_unknown(job.source_id.as_ref())),
+        ("Git tag:", or_unknown(job.git_tag.as_ref())),
+        ("Branch:", or_unknown(job.branch.as_ref())),
+        ("User mail:", or_unknown(job.user_mail.as_ref())),
+        ("Job URL:", or_unknown(job.url.as_ref())),
+    ]
+    .map(|(label, value)| vec![label.to_owned(), value]);
+
+    text_table(&rows)
+}
+
+/// Jobs to cancel because a newer one shares their deduplication key, as configured for
+/// their project. Every job of the kept job pipeline is kept too.
+pub fn duplicated_jobs<'job_info>(
+    config: &Config,
+    jobs: &'job_info HashMap<ProjectID, HashSet<JobInfo>>,
+) -> HashSet<&'job_info JobInfo> {
+    let mut duplicated = HashSet::new();
+
+    for (proj, jobs_) in jobs {
+        let dedup = config.dedup(proj.0);
+
+        let mut groups: HashMap<_, Vec<&JobInfo>> = HashMap::new();
+        for job in jobs_.iter().filter(|job| job.pipeline_id.is_some()) {
+            if let Some(key) = dedup_key(job, dedup.key()) {
+                groups.entry(key).or_default().push(job);
+            }
+        }
+
+        for group in groups.values() {
+            let kept = group
+                .iter()
+                .max_by_key(|job| {
+                    let version = (dedup.keep() == DedupKeep::Semver)
+                        .then(|| tag_version(job))
+                        .flatten();
+                    (version, job.pipeline_id)
+                })
+                .and_then(|job| job.pipeline_id);
+
+            duplicated.extend(group.iter().filter(|job| job.pipeline_id != kept));
+        }
+    }
+
+    duplicated
+}
+
+/// Values two jobs must share to be duplicates of each other, `None` when the job lacks the
+/// keyed field, such a job being no duplicate of any other.
+fn dedup_key(job: &JobInfo, key: DedupKey) -> Option<(Option<u64>, Option<&str>)> {
+    let value = match key {
+        DedupKey::Project => return Some((None, None)),
+        DedupKey::JobName => job.name.as_deref(),
+        DedupKey::Environment => job.environment.as_deref(),
+        DedupKey::Branch => job.branch.as_deref(),
+        DedupKey::SourceTag => job.git_tag.as_deref(),
+    };
+    let source = (key == DedupKey::SourceTag)
+        .then_some(job.source_id)
+        .flatten();
+    Some((source, Some(value?)))
+}
+
+/// Semantic version of the job tag, ignoring a leading `v`.
+fn tag_version(job: &JobInfo) -> Option<Version> {
+    let tag = job.git_tag.as_deref()?;
+    Version::parse(tag.strip_prefix('v').unwrap_or(tag)).ok()
+}
+
+/// Decision for a job whose tag is unknown or breaks the tag rules of its project, `None`
+/// when the tag is fine.
+///
+/// Jobs without tag are only refused when protected tags are required, having nothing
+/// protected to deploy.
+async fn check_tag(
+    api: &GitlabJOB,
+    proj: ProjectID,
+    job: &JobInfo,
+    now: DateTime<Utc>,
+) -> Option<(Decision, Option<MailReason>)> {
+    let Some(tag) = job.git_tag.as_deref() else {
+        if !api
+            .config
+            .tag_rules(proj.0)
+            .is_some_and(TagRules::require_protected)
+        {
+            return None;
+        }
+        job_log!(
+            Warn,
+            JobContext::from(job).decision(Decision::Cancel),
+            "The job {job} will be cancelled, it has no tag and only protected tags are deployed."
+        );
+        return Some((Decision::Cancel, Some(MailReason::UnprotectedTag)));
+    };
+
+    let tags_proj = job.source_id.map_or(proj, ProjectID);
+    let found = match api.get_tag(tags_proj, tag).await {
+        Ok(Some(found)) => found,
+        Ok(None) => {
+            job_log!(
+                Warn,
+                JobContext::from(job).decision(Decision::Cancel),
+                "The job {job} will be cancelled due to invalid tag."
+            );
+            return Some((Decision::Cancel, Some(MailReason::InvalidTag)));
+        }
+        Err(error) => {
+            job_log!(
+                Error,
+                JobContext::from(job).decision(Decision::Defer),
+                "Couldn't check the tag of job {job}, leaving it for later: {error}"
+            );
+            return Some((Decision::Defer, None));
+        }
+    };
+
+    let rules = api.config.tag_rules(proj.0)?;
+    if let Err(violation) = rules.check(&found.name, found.created_at, now) {
+        job_log!(
+            Warn,
+            JobContext::from(job).decision(Decision::Cancel),
+            "The job {job} will be cancelled, its tag {tag} {violation}."
+        );
+        return Some((Decision::Cancel, Some(MailReason::TagRule(violation))));
+    }
+    if !rules.require_protected() {
+        return None;
+    }
+
+    match api.get_protected_tags(tags_proj).await {
+        Ok(protected)
+            if protected
+                .iter()
+                .any(|pattern| JobPattern::glob(pattern).is_ok_and(|glob| glob.matches(tag))) =>
+        {
+            None
+        }
+        Ok(_) => {
+            job_log!(
+                Warn,
+                JobContext::from(job).decision(Decision::Cancel),
+                "The job {job} will be cancelled, its tag {tag} isn't protected."
+            );
+            Some((Decision::Cancel, Some(MailReason::UnprotectedTag)))
+        }
+        Err(error) => {
+            job_log!(
+                Error,
+                JobContext::from(job).decision(Decision::Defer),
+                "Couldn't check the protected tags of job {job}, leaving it for later: {error}"
+            );
+            Some((Decision::Defer, None))
+        }
+    }
+}
+
+/// Check if the job must be canceled, played or deferred.
 pub async fn validate_jobs<'job_info>(
     api: &GitlabJOB,
     proj_jobs: &'job_info HashMap<ProjectID, HashSet<JobInfo>>,
-) -> HashMap<&'job_info JobInfo, (bool, Option<MailReason>)> {
-    let pipes_tocancel = pipelines_tocancel(proj_jobs);
+) -> HashMap<&'job_info JobInfo, (Decision, Option<MailReason>)> {
+    let duplicated = duplicated_jobs(&api.config, proj_jobs);
     let mut checked_jobs = HashMap::new();
+    let now = Utc::now();
 
     for (proj, jobs) in proj_jobs {
         for job in jobs {
-            if pipes_tocancel[proj].contains(&PipelineID(job.pipeline_id.unwrap_or_default())) {
-                warn!(
-                    "The job {} will be canceled due to duplicated pipelines",
-                    job
+            if duplicated.contains(job) {
+                job_log!(
+                    Warn,
+                    JobContext::from(job).decision(Decision::Cancel),
+                    "The job {job} will be canceled due to duplicated pipelines"
                 );
-                checked_jobs.insert(job, (false, Some(MailReason::Duplicated)));
+                checked_jobs.insert(job, (Decision::Cancel, Some(MailReason::Duplicated)));
                 continue;
             }
-            match (job.source_id, &job.git_tag) {
-                (None, Some(tag)) => {
-                    let proj_tags = api.get_tags(*proj).await;
-                    if proj_tags.contains(tag) {
-                        checked_jobs.insert(job, (true, None));
-                    } else {
-                        checked_jobs.insert(job, (false, Some(MailReason::InvalidTag)));
-                        warn!("The job {} will be cancelled due to invalid tag.", job);
-                    }
-                }
-                (Some(source_proj), Some(tag)) => {
-                    let proj_tags = api.get_tags(ProjectID(source_proj)).await;
-                    if proj_tags.contains(tag) {
-                        checked_jobs.insert(job, (true, None));
-                    } else {
-                        checked_jobs.insert(job, (false, Some(MailReason::InvalidTag)));
-                        warn!("The job {} will be cancelled due to invalid tag.", job);
+            if let Some(checked) = check_tag(api, *proj, job, now).await {
+                checked_jobs.insert(job, checked);
+                continue;
+            }
+            if let Some(schedule) = api.config.deploy_schedule(proj.0) {
+                if !schedule.is_open(now) {
+                    match schedule.next_opening(now) {
+                        Some(opening) => job_log!(
+                            Warn,
+                            JobContext::from(job).decision(Decision::Defer),
+                            "The job {job} is outside its deploy window, next window opens at {}",
+                            opening.to_rfc3339()
+                        ),
+                        None => job_log!(
+                            Warn,
+                            JobContext::from(job).decision(Decision::Defer),
+                            "The job {job} is outside its deploy window"
+                        ),
                     }
-                }
-                (Some(_) | None, None) => {
-                    checked_jobs.insert(job, (true, None));
+                    checked_jobs.insert(
+                        job,
+                        (Decision::Defer, Some(MailReason::OutsideDeployWindow)),
+                    );
+                    continue;
                 }
             }
+            checked_jobs.insert(job, (Decision::Play, None));
         }
     }
 
+    apply_needs(&api.config, &mut checked_jobs);
     checked_jobs
 }
+
+/// Jobs of a run that must succeed before a job is played, as configured in `needs`.
+pub fn prerequisites<'job_info, I>(
+    config: &Config,
+    job: &JobInfo,
+    run: I,
+) -> Vec<&'job_info JobInfo>
+where
+    I: IntoIterator<Item = &'job_info JobInfo>,
+{
+    let (Some(graph), Some(proj)) = (config.needs.as_ref(), job.proj_id) else {
+        return vec![];
+    };
+    let needs = graph.needs(proj, job.name.as_deref());
+    if needs.is_empty() {
+        return vec![];
+    }
+
+    run.into_iter()
+        .filter(|&other| {
+            other != job
+                && other.proj_id.is_some_and(|other_proj| {
+                    needs
+                        .iter()
+                        .any(|node| DeployGraph::is_node(node, other_proj, other.name.as_deref()))
+                })
+        })
+        .collect()
+}
+
+/// Cancel the jobs to play whose prerequisites are canceled, and defer the ones whose
+/// prerequisites are deferred, until no more job changes.
+fn apply_needs(
+    config: &Config,
+    checked_jobs: &mut HashMap<&JobInfo, (Decision, Option<MailReason>)>,
+) {
+    loop {
+        let mut changes = vec![];
+        for (&job, _) in checked_jobs
+            .iter()
+            .filter(|&(_, context)| context.0 == Decision::Play)
+        {
+            for prerequisite in prerequisites(config, job, checked_jobs.keys().copied()) {
+                match checked_jobs.get(prerequisite).map(|context| context.0) {
+                    Some(Decision::Cancel) => {
+                        job_log!(
+                            Warn,
+                            JobContext::from(job).decision(Decision::Cancel),
+                            "The job {job} will be canceled, its prerequisite {prerequisite} \
+                            is canceled."
+                        );
+                        let reason = MailReason::PrerequisiteFailed(prerequisite.to_string());
+                        changes.push((job, (Decision::Cancel, Some(reason))));
+                        break;
+                    }
+                    Some(Decision::Defer) => {
+                        job_log!(
+                            Warn,
+                            JobContext::from(job).decision(Decision::Defer),
+                            "The job {job} is deferred with its prerequisite {prerequisite}."
+                        );
+                        changes.push((job, (Decision::Defer, None)));
+                        break;
+                    }
+                    Some(Decision::Play) | None => {}
+                }
+            }
+        }
+
+        if changes.is_empty() {
+            break;
+        }
+        checked_jobs.extend(changes);
+    }
+}